@@ -1,42 +1,55 @@
+use graphics_from_scratch::camera::Camera;
+use graphics_from_scratch::framebuffer::Framebuffer;
+use graphics_from_scratch::light::{default_lights, SurfaceLight};
 use graphics_from_scratch::mat::Mat4;
-use graphics_from_scratch::pixel_renderer::PixelRenderer;
-use graphics_from_scratch::vec::{Vec2, Vec3};
-use graphics_from_scratch::{draw_line, draw_mesh, get_cube_mesh, DrawOptions};
+use graphics_from_scratch::material::AlphaMode;
+use graphics_from_scratch::mesh::Mesh;
+use graphics_from_scratch::shadow::{render_shadow_maps, Shadows};
+use graphics_from_scratch::texture::TextureFilter;
+use graphics_from_scratch::vec::{Vec2, Vec3, Vec4};
+use graphics_from_scratch::{
+    draw_line, draw_mesh, draw_meshes, draw_triangle_color, draw_triangle_texture, DrawOptions,
+    MeshPosition, Shading, TriangleFill, TriangleLight, World,
+};
 use rand::Rng;
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
 
 fn rendering_benchmarks(c: &mut criterion::Criterion) {
     let width = 1000;
     let height = 1000;
-    let mut cube_mesh = get_cube_mesh();
-    let mut pixel_renderer = PixelRenderer::new_for_benchmark(width, height);
-    let draw_options = DrawOptions {
-        draw_wireframe: true,
-        fill_triangles: true,
-        backface_culling: true,
-        pause_rendering: true,
+    let cube_mesh = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap();
+    let mut framebuffer = Framebuffer::new(width, height, PixelFormatEnum::ARGB8888);
+    let world = World {
+        meshes: vec![MeshPosition::new(cube_mesh, Vec3::new(0.0, 0.0, 0.0))],
+        camera: Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0)),
+        options: DrawOptions {
+            draw_wireframe: true,
+            triangle_fill: TriangleFill::Texture,
+            shading: Shading::Flat,
+            backface_culling: true,
+            pause_rendering: true,
+            shuffle_faces: true,
+            tiled: false,
+            texture_filter: TextureFilter::Nearest,
+            shadows: Shadows::None,
+        },
+        lights: default_lights(),
     };
 
     ////////////////////////////////////////////
-    // Pixel Renderer benchmarks
+    // Framebuffer benchmarks
     ////////////////////////////////////////////
-    let mut bench_group = c.benchmark_group("PixelRenderer");
+    let mut bench_group = c.benchmark_group("Framebuffer");
 
     bench_group.bench_function("set_pixel", |b| {
         b.iter(|| {
-            pixel_renderer.set_pixel(500, 500, Color::RGB(50, 100, 150));
+            framebuffer.set_pixel(500, 500, Color::RGB(50, 100, 150));
         })
     });
 
     bench_group.bench_function("clear_pixels", |b| {
         b.iter(|| {
-            pixel_renderer.clear_pixels(Color::RGB(50, 100, 150));
-        })
-    });
-
-    bench_group.bench_function("render", |b| {
-        b.iter(|| {
-            pixel_renderer.render();
+            framebuffer.clear_pixels(Color::RGB(50, 100, 150));
         })
     });
 
@@ -47,24 +60,147 @@ fn rendering_benchmarks(c: &mut criterion::Criterion) {
     ////////////////////////////////////////////
     let mut bench_group = c.benchmark_group("draw");
 
+    let shadow_maps = render_shadow_maps(&world);
     bench_group.bench_function("draw_mesh", |b| {
         b.iter(|| {
-            draw_mesh(&mut pixel_renderer, &draw_options, &mut cube_mesh);
+            draw_mesh(&mut framebuffer, &world, &shadow_maps, &world.meshes[0]);
         })
     });
 
     bench_group.bench_function("draw_line", |b| {
         b.iter(|| {
             draw_line(
-                &mut pixel_renderer,
+                &mut framebuffer,
                 Color::RGB(0, 0, 0),
-                Vec2::new(100.0, 200.0),
-                Vec2::new(300.0, 400.0),
+                Vec4::new(100.0, 200.0, 0.0, 1.0),
+                Vec4::new(300.0, 400.0, 0.0, 1.0),
             );
         })
     });
 
     bench_group.finish();
+
+    ////////////////////////////////////////////
+    // Triangle rasterization benchmarks
+    ////////////////////////////////////////////
+    let mut bench_group = c.benchmark_group("triangle");
+
+    // The depth buffer is reset every iteration, so every covered pixel is drawn.
+    let mut framebuffer = Framebuffer::new(256, 256, PixelFormatEnum::ARGB8888);
+    let texture = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png")
+        .unwrap()
+        .texture;
+    let light = TriangleLight::Flat(SurfaceLight {
+        diffuse: Vec3::new(1.0, 1.0, 1.0),
+        specular: Vec3::new(0.0, 0.0, 0.0),
+    });
+    let uvs = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(0.0, 1.0),
+    ];
+    let triangles = [
+        (
+            "large",
+            [
+                Vec4::new(2.0, 3.0, 1.0, 1.0),
+                Vec4::new(250.0, 10.0, 2.0, 2.0),
+                Vec4::new(20.0, 252.0, 3.0, 3.0),
+            ],
+        ),
+        (
+            "sliver",
+            [
+                Vec4::new(2.0, 3.0, 1.0, 1.0),
+                Vec4::new(250.0, 240.0, 2.0, 2.0),
+                Vec4::new(245.0, 252.0, 3.0, 3.0),
+            ],
+        ),
+    ];
+
+    for (name, [a, b, c]) in triangles {
+        bench_group.bench_function(format!("color {}", name), |bencher| {
+            bencher.iter(|| {
+                framebuffer.z_buffer.fill(f32::INFINITY);
+                draw_triangle_color(
+                    &mut framebuffer,
+                    Color::RGB(50, 100, 150),
+                    AlphaMode::Opaque,
+                    light,
+                    a,
+                    b,
+                    c,
+                );
+            })
+        });
+
+        for (filter_name, filter) in [
+            ("", TextureFilter::Nearest),
+            (" bilinear", TextureFilter::Bilinear),
+            (" trilinear", TextureFilter::Trilinear),
+        ] {
+            bench_group.bench_function(format!("texture{} {}", filter_name, name), |bencher| {
+                bencher.iter(|| {
+                    framebuffer.z_buffer.fill(f32::INFINITY);
+                    draw_triangle_texture(
+                        &mut framebuffer,
+                        &texture,
+                        filter,
+                        1.0,
+                        AlphaMode::Opaque,
+                        light,
+                        a,
+                        b,
+                        c,
+                        uvs[0],
+                        uvs[1],
+                        uvs[2],
+                    );
+                })
+            });
+        }
+    }
+
+    bench_group.finish();
+
+    ////////////////////////////////////////////
+    // Tiled rendering benchmarks
+    ////////////////////////////////////////////
+    let mut bench_group = c.benchmark_group("tiled");
+
+    for model in ["crab", "drone"] {
+        let mesh = Mesh::load_mesh(
+            &format!("./assets/{}.obj", model),
+            &format!("./assets/{}.png", model),
+        )
+        .unwrap();
+        let mut world = World {
+            meshes: vec![MeshPosition::new(mesh, Vec3::new(0.0, 0.0, 0.0))],
+            camera: Camera::new(Vec3::new(0.0, 1.0, -2.5), Vec3::new(0.0, 0.0, 0.0)),
+            options: DrawOptions {
+                draw_wireframe: false,
+                triangle_fill: TriangleFill::Texture,
+                shading: Shading::Phong,
+                backface_culling: true,
+                pause_rendering: true,
+                shuffle_faces: false,
+                tiled: false,
+                texture_filter: TextureFilter::Nearest,
+                shadows: Shadows::None,
+            },
+            lights: default_lights(),
+        };
+
+        for tiled in [false, true] {
+            world.options.tiled = tiled;
+            let name = format!("{} {}", model, if tiled { "tiled" } else { "serial" });
+            bench_group.bench_function(name, |b| {
+                b.iter(|| draw_meshes(&mut framebuffer, &world));
+            });
+        }
+    }
+
+    bench_group.finish();
 }
 
 fn math_benchmarks(c: &mut criterion::Criterion) {
@@ -117,9 +253,31 @@ fn math_benchmarks(c: &mut criterion::Criterion) {
         b.iter(|| mat_a * mat_b);
     });
 
+    bench_group.bench_function("mat4 mat4 mul scalar", |b| {
+        b.iter(|| mat_a.mul_mat4_scalar(&mat_b));
+    });
+
+    let vec = Vec4::new(
+        rng.gen_range(-3.0..3.0),
+        rng.gen_range(-3.0..3.0),
+        rng.gen_range(-3.0..3.0),
+        1.0,
+    );
+
+    bench_group.bench_function("mat4 vec4 mul", |b| {
+        b.iter(|| mat_a * vec);
+    });
+
+    bench_group.bench_function("mat4 vec4 mul scalar", |b| {
+        b.iter(|| mat_a.mul_vec4_scalar(vec));
+    });
+
+    let projection_matrix =
+        Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0)).projection_matrix(1.0);
     bench_group.bench_function("project_point_to_screen_space", |b| {
         b.iter(|| {
             graphics_from_scratch::project_point_to_screen_space(
+                projection_matrix,
                 rng.gen_range(0..300),
                 rng.gen_range(0..300),
                 Vec3::new(
@@ -0,0 +1,25 @@
+// Convert a mesh between the OBJ, STL and PLY formats, choosing the formats from the extensions:
+//   cargo run --example convert -- input.stl output.ply
+use graphics_from_scratch::mesh::Mesh;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <input> <output>", args[0]);
+        std::process::exit(2);
+    }
+
+    let mesh = Mesh::load(&args[1]).unwrap_or_else(|e| {
+        eprintln!("Could not load mesh: {}", e);
+        std::process::exit(1);
+    });
+    if let Err(e) = mesh.save(&args[2]) {
+        eprintln!("Could not save {}: {}", args[2], e);
+        std::process::exit(1);
+    }
+    println!(
+        "Converted {} vertices and {} faces",
+        mesh.vertices.len(),
+        mesh.faces.len()
+    );
+}
@@ -0,0 +1,195 @@
+use crate::mat::Mat4;
+use crate::quat::Quat;
+use crate::vec::Vec3;
+use crate::UP;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Projection {
+    // Things further away look smaller. The view is a pyramid, set by the field of view.
+    Perspective,
+    // Things look the same size at any distance. The view is a box, set by the extent.
+    Orthographic,
+}
+
+/// A camera at location, looking along +z rotated by orientation. The aspect ratio is not part of
+/// the camera, it comes from the size of the framebuffer that is drawn into, so the same camera
+/// works for any window size.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Camera {
+    pub location: Vec3,
+    // The rotation from camera space to world space. Camera space looks along +z, with +y up.
+    pub orientation: Quat,
+    pub projection: Projection,
+    // The vertical field of view in degrees, for perspective projection.
+    pub fov: f32,
+    // The height of the view in world units, for orthographic projection.
+    pub extent: f32,
+    // Distances from the camera to the near and far clip planes.
+    pub z_near: f32,
+    pub z_far: f32,
+}
+
+impl Camera {
+    pub fn new(location: Vec3, look_at: Vec3) -> Self {
+        Self {
+            location,
+            orientation: Quat::look_rotation(look_at - location, UP),
+            projection: Projection::Perspective,
+            fov: 60.0,
+            extent: 4.0,
+            z_near: 0.01,
+            z_far: 10.0,
+        }
+    }
+
+    /// Turn the camera towards target, keeping it level.
+    pub fn look_at(&mut self, target: Vec3) {
+        self.orientation = Quat::look_rotation(target - self.location, UP);
+    }
+
+    /// The direction the camera looks in, in world space.
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * Vec3::new(0.0, 0.0, 1.0)
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.orientation * Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.orientation * Vec3::new(0.0, 1.0, 0.0)
+    }
+
+    /// The matrix from world space to camera space, where the camera looks along +z.
+    pub fn view_matrix(&self) -> Mat4 {
+        // Move the camera to the origin, then undo its rotation.
+        self.orientation.conjugate().to_mat4()
+            * Mat4::translate(-self.location.x, -self.location.y, -self.location.z)
+    }
+
+    /// The matrix from camera space to clip space. aspect_ratio is width / height.
+    /// After dividing by w, x and y are -1.0 to 1.0 (y pointing down) and z is 0.0 at the near plane
+    /// and 1.0 at the far plane. With perspective projection w is the distance from the camera,
+    /// with orthographic projection it is always 1.0.
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> Mat4 {
+        match self.projection {
+            Projection::Perspective => {
+                Mat4::perspective(self.fov, aspect_ratio, self.z_near, self.z_far)
+            }
+            Projection::Orthographic => {
+                let half_height = self.extent / 2.0;
+                let half_width = half_height * aspect_ratio;
+                Mat4::orthographic(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.z_near,
+                    self.z_far,
+                )
+            }
+        }
+    }
+
+    /// The height of the perspective view at the given distance from the camera. Switching to
+    /// orthographic projection with this extent keeps what is at that distance the same size.
+    pub fn extent_at_distance(&self, distance: f32) -> f32 {
+        2.0 * distance * (self.fov / 2.0).to_radians().tan()
+    }
+
+    /// The horizontal field of view in degrees, for a render target with the given aspect ratio.
+    pub fn horizontal_fov(&self, aspect_ratio: f32) -> f32 {
+        // We can't simply multiply angles by the aspect ratio.
+        // We need to convert to triangle ratios and scale one side the the triangle. Time for trig.
+        // The math:
+        // tan(fov / 2) = h / d; where h is the screen height and d is the camera distance from the screen (d will not matter much)
+        // tan(?) = w / d; we want to find ?
+        // a = w / h
+        // w = h * a
+        // (h / d) * a = w / d
+        // atan(w / d) = ?; this is what we want
+        ((self.fov / 2.0).to_radians().tan() * aspect_ratio)
+            .atan()
+            .to_degrees()
+            * 2.0
+    }
+}
+
+#[test]
+fn test_camera_projection_matrix() {
+    let camera = Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0));
+    let projection = camera.projection_matrix(2.0);
+
+    // A point on the near plane at the top right corner of the view.
+    let half_height = camera.z_near * (camera.fov / 2.0).to_radians().tan();
+    let p = projection * Vec3::new(half_height * 2.0, half_height, camera.z_near).to_vec4();
+    assert!((p.x / p.w - 1.0).abs() < 1e-5);
+    assert!((p.y / p.w + 1.0).abs() < 1e-5);
+    assert!((p.z / p.w).abs() < 1e-5);
+    assert_eq!(p.w, camera.z_near);
+
+    let p = projection * Vec3::new(0.0, 0.0, camera.z_far).to_vec4();
+    assert!((p.z / p.w - 1.0).abs() < 1e-5);
+
+    // A square render target sees as far horizontally as vertically.
+    assert!((camera.horizontal_fov(1.0) - camera.fov).abs() < 1e-4);
+    assert!(camera.horizontal_fov(2.0) > camera.fov);
+}
+
+#[test]
+fn test_camera_orthographic_projection_matrix() {
+    let camera = Camera {
+        projection: Projection::Orthographic,
+        extent: 2.0,
+        ..Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0))
+    };
+    let projection = camera.projection_matrix(2.0);
+
+    // The top right corner of the view is the same at any distance.
+    for z in [camera.z_near, 1.0, camera.z_far] {
+        let p = projection * Vec3::new(2.0, 1.0, z).to_vec4();
+        assert_eq!(p.w, 1.0);
+        assert!((p.x - 1.0).abs() < 1e-6);
+        assert!((p.y + 1.0).abs() < 1e-6);
+    }
+    let near = projection * Vec3::new(0.0, 0.0, camera.z_near).to_vec4();
+    let far = projection * Vec3::new(0.0, 0.0, camera.z_far).to_vec4();
+    assert!(near.z.abs() < 1e-6);
+    assert!((far.z - 1.0).abs() < 1e-6);
+
+    // Switching projection at the distance of the origin keeps the size of things there.
+    let camera = Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0));
+    let perspective = camera.projection_matrix(1.0) * Vec3::new(0.0, 1.0, 5.0).to_vec4();
+    let orthographic = Camera {
+        projection: Projection::Orthographic,
+        extent: camera.extent_at_distance(5.0),
+        ..camera
+    }
+    .projection_matrix(1.0)
+        * Vec3::new(0.0, 1.0, 5.0).to_vec4();
+    assert!((perspective.y / perspective.w - orthographic.y / orthographic.w).abs() < 1e-5);
+}
+
+#[test]
+fn test_camera_view_matrix() {
+    let target = Vec3::new(1.0, 2.0, 3.0);
+    let mut camera = Camera::new(Vec3::new(-2.0, 4.0, -1.0), Vec3::new(0.0, 0.0, 0.0));
+    camera.look_at(target);
+
+    // The same view as the look_at matrix, built from the orientation instead.
+    let expected = Mat4::look_at(camera.location, target, UP);
+    let view = camera.view_matrix();
+    for row in 0..4 {
+        for column in 0..4 {
+            assert!((view.get(row, column) - expected.get(row, column)).abs() < 1e-5);
+        }
+    }
+    let distance = (target - camera.location).len();
+    assert!((view * target - Vec3::new(0.0, 0.0, distance)).len() < 1e-5);
+
+    // Looking straight down is fine too.
+    camera.look_at(camera.location - UP);
+    assert!((camera.forward() + UP).len() < 1e-6);
+    assert!(camera.right().dot(UP).abs() < 1e-6);
+    assert!(camera.up().dot(UP).abs() < 1e-6);
+}
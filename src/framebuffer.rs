@@ -0,0 +1,322 @@
+// The framebuffer owns the color and depth buffers that all drawing functions write into.
+// It does not depend on a window or an initialized SDL context, so it can be drawn into
+// headlessly (tests, benchmarks, offscreen rendering). A PixelRenderer can present it to a window.
+use image::{ImageBuffer, Rgba};
+use sdl2::pixels::{Color, PixelFormat, PixelFormatEnum};
+
+pub const SIZE_OF_COLOR: usize = std::mem::size_of::<Color>();
+
+/// Something the draw functions can draw into: the whole framebuffer, or one tile of it.
+pub trait RenderTarget {
+    /// The pixels that can be drawn, as (x_min, y_min, x_max, y_max), inclusive.
+    /// Pixels outside these bounds are ignored, so drawing code can skip them.
+    fn bounds(&self) -> (i32, i32, i32, i32);
+    fn set_pixel(&mut self, x: u32, y: u32, color: Color);
+    /// Set the pixel only if z is closer than what was drawn there before.
+    fn set_pixel_z(&mut self, x: u32, y: u32, z: f32, color: Color);
+    /// Blend the color over the pixel by its alpha, only if z is closer than what was drawn there
+    /// before. The depth is left as it is, so things behind can still be blended in later.
+    fn blend_pixel_z(&mut self, x: u32, y: u32, z: f32, color: Color);
+}
+
+// The color over another color, by its alpha.
+pub fn blend_over(color: Color, below: Color) -> Color {
+    let alpha = color.a as f32 / 255.0;
+    let mix =
+        |over: u8, under: u8| (over as f32 * alpha + under as f32 * (1.0 - alpha)).round() as u8;
+    Color::RGBA(
+        mix(color.r, below.r),
+        mix(color.g, below.g),
+        mix(color.b, below.b),
+        mix(255, below.a),
+    )
+}
+
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub color_buffer: Box<[u8]>,
+    pub z_buffer: Box<[f32]>,
+    pub pixel_format_enum: PixelFormatEnum,
+    pub pixel_format: PixelFormat,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32, pixel_format_enum: PixelFormatEnum) -> Self {
+        assert!(pixel_format_enum.byte_size_per_pixel() == SIZE_OF_COLOR);
+        let pixel_count: usize = (width * height) as usize;
+        let color_buffer: Box<[u8]> = vec![0u8; pixel_count * SIZE_OF_COLOR].into_boxed_slice();
+        let z_buffer: Box<[f32]> = vec![f32::INFINITY; pixel_count].into_boxed_slice();
+        let pixel_format: PixelFormat = pixel_format_enum.try_into().unwrap();
+        Self {
+            width,
+            height,
+            color_buffer,
+            z_buffer,
+            pixel_format_enum,
+            pixel_format,
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let i = ((y * self.width) + x) as usize * SIZE_OF_COLOR;
+
+        // Turn the color into bytes. The correct bytes for a color depend on
+        // the pixel format and the system endianness.
+        let color_bytes: &[u8; SIZE_OF_COLOR] = &color.to_u32(&self.pixel_format).to_ne_bytes();
+
+        self.color_buffer[i..i + SIZE_OF_COLOR].copy_from_slice(color_bytes);
+    }
+
+    pub fn set_pixel_z(&mut self, x: u32, y: u32, z: f32, color: Color) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let i = ((y * self.width) + x) as usize;
+
+        if z >= self.z_buffer[i] {
+            return;
+        } else {
+            self.z_buffer[i] = z;
+        }
+
+        let i_color = i * SIZE_OF_COLOR;
+
+        // Turn the color into bytes. The correct bytes for a color depend on
+        // the pixel format and the system endianness.
+        let color_bytes: &[u8; SIZE_OF_COLOR] = &color.to_u32(&self.pixel_format).to_ne_bytes();
+
+        self.color_buffer[i_color..i_color + SIZE_OF_COLOR].copy_from_slice(color_bytes);
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        if x >= self.width || y >= self.height {
+            panic!("Pixel {} {} is out of range for Framebuffer", x, y);
+        }
+
+        let i = ((y * self.width) + x) as usize * SIZE_OF_COLOR;
+        let mut color_bytes = [0u8; SIZE_OF_COLOR];
+        color_bytes.copy_from_slice(&self.color_buffer[i..i + SIZE_OF_COLOR]);
+        Color::from_u32(&self.pixel_format, u32::from_ne_bytes(color_bytes))
+    }
+
+    pub fn blend_pixel_z(&mut self, x: u32, y: u32, z: f32, color: Color) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        if z >= self.z_buffer[((y * self.width) + x) as usize] {
+            return;
+        }
+        let below = self.get_pixel(x, y);
+        self.set_pixel(x, y, blend_over(color, below));
+    }
+
+    /// Convert the color buffer into an RGBA image.
+    /// The color buffer is stored in the pixel format of the framebuffer with native endianness,
+    /// so every pixel has to be decoded before it can be written to a file.
+    pub fn to_image(&self) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let color = self.get_pixel(x, y);
+            Rgba([color.r, color.g, color.b, color.a])
+        })
+    }
+
+    pub fn save_png(&self, file_path: &str) -> image::ImageResult<()> {
+        self.to_image()
+            .save_with_format(file_path, image::ImageFormat::Png)
+    }
+
+    pub fn save_bmp(&self, file_path: &str) -> image::ImageResult<()> {
+        self.to_image()
+            .save_with_format(file_path, image::ImageFormat::Bmp)
+    }
+
+    /// Write a binary (P6) PPM file. PPM has no alpha channel, so alpha is dropped.
+    pub fn save_ppm(&self, file_path: &str) -> std::io::Result<()> {
+        use std::fs::File;
+        use std::io::{BufWriter, Write};
+
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        self.write_ppm(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn write_ppm<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let mut row: Vec<u8> = Vec::with_capacity(self.width as usize * 3);
+        for y in 0..self.height {
+            row.clear();
+            for x in 0..self.width {
+                let color = self.get_pixel(x, y);
+                row.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+            writer.write_all(&row)?;
+        }
+        Ok(())
+    }
+
+    /// Save the frame, choosing the file format from the file extension (png, bmp or ppm).
+    pub fn save(&self, file_path: &str) -> Result<(), String> {
+        let extension = std::path::Path::new(file_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("png") => self.save_png(file_path).map_err(|e| e.to_string()),
+            Some("bmp") => self.save_bmp(file_path).map_err(|e| e.to_string()),
+            Some("ppm") => self.save_ppm(file_path).map_err(|e| e.to_string()),
+            _ => Err(format!("Unsupported image file extension: {}", file_path)),
+        }
+    }
+
+    pub fn clear_pixels(&mut self, color: Color) {
+        let color_bytes: &[u8; SIZE_OF_COLOR] = &color.to_u32(&self.pixel_format).to_ne_bytes();
+
+        let width: usize = self.width as usize;
+        let height: usize = self.height as usize;
+
+        for y in 0_usize..height {
+            for x in 0_usize..width {
+                let i: usize = (y * width) + x;
+                self.z_buffer[i] = f32::INFINITY;
+                let i_color: usize = i * SIZE_OF_COLOR;
+                self.color_buffer[i_color..i_color + SIZE_OF_COLOR].copy_from_slice(color_bytes);
+            }
+        }
+    }
+}
+
+impl RenderTarget for Framebuffer {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        (0, 0, self.width as i32 - 1, self.height as i32 - 1)
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        Framebuffer::set_pixel(self, x, y, color);
+    }
+
+    fn set_pixel_z(&mut self, x: u32, y: u32, z: f32, color: Color) {
+        Framebuffer::set_pixel_z(self, x, y, z, color);
+    }
+
+    fn blend_pixel_z(&mut self, x: u32, y: u32, z: f32, color: Color) {
+        Framebuffer::blend_pixel_z(self, x, y, z, color);
+    }
+}
+
+#[test]
+fn test_framebuffer_set_get_pixel() {
+    let mut framebuffer = Framebuffer::new(4, 3, PixelFormatEnum::ARGB8888);
+    framebuffer.clear_pixels(Color::RGB(1, 2, 3));
+    assert_eq!(framebuffer.get_pixel(3, 2), Color::RGB(1, 2, 3));
+
+    framebuffer.set_pixel(3, 2, Color::RGB(200, 100, 50));
+    assert_eq!(framebuffer.get_pixel(3, 2), Color::RGB(200, 100, 50));
+    assert_eq!(framebuffer.get_pixel(2, 2), Color::RGB(1, 2, 3));
+
+    // Out of range pixels are ignored.
+    framebuffer.set_pixel(4, 0, Color::RGB(200, 100, 50));
+    framebuffer.set_pixel(0, 3, Color::RGB(200, 100, 50));
+}
+
+#[test]
+fn test_framebuffer_set_pixel_z() {
+    let mut framebuffer = Framebuffer::new(4, 3, PixelFormatEnum::ARGB8888);
+    framebuffer.clear_pixels(Color::RGB(0, 0, 0));
+
+    framebuffer.set_pixel_z(1, 1, 5.0, Color::RGB(255, 0, 0));
+    assert_eq!(framebuffer.get_pixel(1, 1), Color::RGB(255, 0, 0));
+
+    // Further away, should be hidden.
+    framebuffer.set_pixel_z(1, 1, 6.0, Color::RGB(0, 255, 0));
+    assert_eq!(framebuffer.get_pixel(1, 1), Color::RGB(255, 0, 0));
+
+    // Closer, should be drawn.
+    framebuffer.set_pixel_z(1, 1, 4.0, Color::RGB(0, 0, 255));
+    assert_eq!(framebuffer.get_pixel(1, 1), Color::RGB(0, 0, 255));
+
+    framebuffer.clear_pixels(Color::RGB(0, 0, 0));
+    assert_eq!(framebuffer.z_buffer[5], f32::INFINITY);
+}
+
+#[test]
+fn test_framebuffer_blend_pixel_z() {
+    let mut framebuffer = Framebuffer::new(4, 3, PixelFormatEnum::ARGB8888);
+    framebuffer.clear_pixels(Color::RGB(0, 0, 0));
+    framebuffer.set_pixel_z(1, 1, 5.0, Color::RGB(0, 0, 200));
+
+    // In front: blended, without moving the depth.
+    framebuffer.blend_pixel_z(1, 1, 4.0, Color::RGBA(200, 0, 0, 64));
+    assert_eq!(framebuffer.get_pixel(1, 1), Color::RGB(50, 0, 150));
+    assert_eq!(framebuffer.z_buffer[5], 5.0);
+
+    // Behind: hidden.
+    framebuffer.blend_pixel_z(1, 1, 6.0, Color::RGBA(0, 255, 0, 255));
+    assert_eq!(framebuffer.get_pixel(1, 1), Color::RGB(50, 0, 150));
+
+    assert_eq!(
+        blend_over(Color::RGBA(255, 255, 255, 0), Color::RGB(1, 2, 3)),
+        Color::RGB(1, 2, 3)
+    );
+    assert_eq!(
+        blend_over(Color::RGBA(10, 20, 30, 255), Color::RGB(1, 2, 3)),
+        Color::RGB(10, 20, 30)
+    );
+}
+
+#[test]
+fn test_framebuffer_to_image() {
+    for pixel_format_enum in [
+        PixelFormatEnum::ARGB8888,
+        PixelFormatEnum::RGBA8888,
+        PixelFormatEnum::ABGR8888,
+        PixelFormatEnum::RGB888,
+    ] {
+        let mut framebuffer = Framebuffer::new(3, 2, pixel_format_enum);
+        framebuffer.clear_pixels(Color::RGB(0, 0, 0));
+        framebuffer.set_pixel(2, 1, Color::RGB(10, 20, 30));
+
+        let image = framebuffer.to_image();
+        assert_eq!(image.dimensions(), (3, 2));
+        assert_eq!(*image.get_pixel(2, 1), Rgba([10, 20, 30, 255]));
+        assert_eq!(*image.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+    }
+}
+
+#[test]
+fn test_framebuffer_write_ppm() {
+    let mut framebuffer = Framebuffer::new(2, 1, PixelFormatEnum::ARGB8888);
+    framebuffer.clear_pixels(Color::RGB(1, 2, 3));
+    framebuffer.set_pixel(1, 0, Color::RGB(4, 5, 6));
+
+    let mut bytes: Vec<u8> = Vec::new();
+    framebuffer.write_ppm(&mut bytes).unwrap();
+
+    let mut expected: Vec<u8> = b"P6\n2 1\n255\n".to_vec();
+    expected.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_framebuffer_save() {
+    let mut framebuffer = Framebuffer::new(3, 2, PixelFormatEnum::ARGB8888);
+    framebuffer.clear_pixels(Color::RGB(0, 0, 0));
+    framebuffer.set_pixel(1, 1, Color::RGB(10, 20, 30));
+
+    let dir = std::env::temp_dir();
+    for extension in ["png", "bmp", "ppm"] {
+        let file_path = dir.join(format!("test_framebuffer_save.{}", extension));
+        let file_path = file_path.to_str().unwrap();
+        framebuffer.save(file_path).unwrap();
+        let image = image::open(file_path).unwrap().into_rgba8();
+        assert_eq!(image, framebuffer.to_image());
+        std::fs::remove_file(file_path).unwrap();
+    }
+
+    assert!(framebuffer.save("frame.gif").is_err());
+}
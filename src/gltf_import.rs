@@ -0,0 +1,463 @@
+use crate::mat::Mat4;
+use crate::material::{AlphaMode, Material};
+use crate::mesh::{default_texture, generate_vertex_normals, Face, Mesh, MeshLoadError};
+use crate::texture::{RgbaImage, Texture, TextureWrap};
+use crate::vec::{Vec2, Vec3};
+use crate::MeshPosition;
+use image::{ImageBuffer, Rgba};
+use sdl2::pixels::Color;
+use std::collections::HashMap;
+
+fn gltf_error(path: &str, message: String) -> MeshLoadError {
+    MeshLoadError::Gltf {
+        path: path.to_string(),
+        message,
+    }
+}
+
+/// Load the meshes of the default scene of a glTF 2.0 file (.gltf with embedded or external
+/// buffers, or .glb). Every node with a mesh becomes a MeshPosition. The rotation and scale of
+/// the node (and its parents) are applied to the vertices, and the translation becomes the position.
+pub fn load_gltf(file_path: &str) -> Result<Vec<MeshPosition>, MeshLoadError> {
+    let (document, buffers, images) =
+        gltf::import(file_path).map_err(|e| gltf_error(file_path, e.to_string()))?;
+    let textures: Vec<RgbaImage> = images
+        .iter()
+        .map(|image| convert_image(file_path, image))
+        .collect::<Result<_, _>>()?;
+    let buffers: Vec<&[u8]> = buffers.iter().map(|buffer| &buffer.0[..]).collect();
+
+    let mut mesh_positions: Vec<MeshPosition> = Vec::new();
+    let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    else {
+        return Ok(mesh_positions);
+    };
+    for node in scene.nodes() {
+        load_node(
+            file_path,
+            &node,
+            Mat4::identity(),
+            &buffers,
+            &textures,
+            &mut mesh_positions,
+        )?;
+    }
+    Ok(mesh_positions)
+}
+
+fn load_node(
+    file_path: &str,
+    node: &gltf::Node,
+    parent_transform: Mat4,
+    buffers: &[&[u8]],
+    textures: &[RgbaImage],
+    mesh_positions: &mut Vec<MeshPosition>,
+) -> Result<(), MeshLoadError> {
+    // glTF matrices are column major.
+    let m = node.transform().matrix();
+    #[rustfmt::skip]
+    let local_transform = Mat4::new(
+        m[0][0], m[1][0], m[2][0], m[3][0],
+        m[0][1], m[1][1], m[2][1], m[3][1],
+        m[0][2], m[1][2], m[2][2], m[3][2],
+        m[0][3], m[1][3], m[2][3], m[3][3],
+    );
+    let transform = parent_transform * local_transform;
+
+    if let Some(mesh) = node.mesh() {
+        mesh_positions.push(load_mesh(file_path, &mesh, transform, buffers, textures)?);
+    }
+    for child in node.children() {
+        load_node(
+            file_path,
+            &child,
+            transform,
+            buffers,
+            textures,
+            mesh_positions,
+        )?;
+    }
+    Ok(())
+}
+
+fn load_mesh(
+    file_path: &str,
+    mesh: &gltf::Mesh,
+    transform: Mat4,
+    buffers: &[&[u8]],
+    textures: &[RgbaImage],
+) -> Result<MeshPosition, MeshLoadError> {
+    let position = Vec3::new(
+        transform.get(0, 3),
+        transform.get(1, 3),
+        transform.get(2, 3),
+    );
+    let columns = [0, 1, 2].map(|c| {
+        Vec3::new(
+            transform.get(0, c),
+            transform.get(1, c),
+            transform.get(2, c),
+        )
+    });
+    // Normals must be transformed by the inverse transpose of the transform. The cofactor matrix
+    // (whose columns are these cross products) is the inverse transpose multiplied by the determinant.
+    let normal_columns = [
+        columns[1].cross(columns[2]),
+        columns[2].cross(columns[0]),
+        columns[0].cross(columns[1]),
+    ];
+    let determinant = columns[0].dot(normal_columns[0]);
+    // A transform with a negative determinant mirrors the mesh, which reverses the winding order.
+    let mirrored = determinant < 0.0;
+
+    let mut vertices: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut faces: Vec<Face> = Vec::new();
+    let mut materials: Vec<Material> = vec![Material::default()];
+    let mut material_indices: HashMap<usize, usize> = HashMap::new();
+
+    for primitive in mesh.primitives() {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            continue;
+        }
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).copied());
+        let Some(positions) = reader.read_positions() else {
+            continue;
+        };
+
+        // Every vertex gets its own UV and normal, so all three lists share indices.
+        let base = vertices.len();
+        vertices
+            .extend(positions.map(|p| columns[0] * p[0] + columns[1] * p[1] + columns[2] * p[2]));
+        let count = vertices.len() - base;
+
+        match reader.read_tex_coords(0) {
+            // glTF puts the UV origin in the top left corner, we put it in the bottom left corner.
+            Some(tex_coords) => uvs.extend(
+                tex_coords
+                    .into_f32()
+                    .map(|uv| Vec2::new(uv[0], 1.0 - uv[1])),
+            ),
+            None => uvs.extend(std::iter::repeat_n(Vec2::new(0.0, 0.0), count)),
+        }
+        if uvs.len() != vertices.len() {
+            return Err(gltf_error(
+                file_path,
+                format!(
+                    "TEXCOORD_0 count does not match POSITION count in mesh {}",
+                    mesh.index()
+                ),
+            ));
+        }
+
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..count).collect(),
+        };
+        if let Some(&index) = indices.iter().find(|&&i| i >= count) {
+            return Err(gltf_error(
+                file_path,
+                format!("index {} is out of range in mesh {}", index, mesh.index()),
+            ));
+        }
+
+        let material = match primitive.material().index() {
+            None => 0,
+            Some(gltf_index) => *material_indices.entry(gltf_index).or_insert_with(|| {
+                materials.push(convert_material(&primitive.material(), textures));
+                materials.len() - 1
+            }),
+        };
+
+        let first_face = faces.len();
+        for triangle in indices.chunks_exact(3) {
+            let (a, mut b, mut c) = (base + triangle[0], base + triangle[1], base + triangle[2]);
+            if mirrored {
+                std::mem::swap(&mut b, &mut c);
+            }
+            faces.push(Face {
+                material,
+                ..Face::new(a, b, c, a, b, c)
+            });
+        }
+
+        match reader.read_normals() {
+            Some(primitive_normals) => normals.extend(primitive_normals.map(|n| {
+                let n =
+                    normal_columns[0] * n[0] + normal_columns[1] * n[1] + normal_columns[2] * n[2];
+                if mirrored {
+                    -n.unit_norm()
+                } else {
+                    n.unit_norm()
+                }
+            })),
+            None => {
+                let generated = generate_vertex_normals(&vertices, &faces[first_face..]);
+                normals.extend_from_slice(&generated[base..]);
+            }
+        }
+        if normals.len() != vertices.len() {
+            return Err(gltf_error(
+                file_path,
+                format!(
+                    "NORMAL count does not match POSITION count in mesh {}",
+                    mesh.index()
+                ),
+            ));
+        }
+    }
+
+    let mesh = Mesh {
+        vertices,
+        uvs,
+        normals,
+        faces,
+        materials,
+        texture: default_texture(),
+        colors: Vec::new(),
+    };
+    Ok(MeshPosition::new(mesh, position))
+}
+
+fn float_to_u8(f: f32) -> u8 {
+    (f * 255.0).clamp(0.0, 255.0).round() as u8
+}
+
+fn solid_texture(color: Color) -> RgbaImage {
+    ImageBuffer::from_pixel(1, 1, Rgba([color.r, color.g, color.b, 255]))
+}
+
+fn convert_wrap(mode: gltf::texture::WrappingMode) -> TextureWrap {
+    match mode {
+        gltf::texture::WrappingMode::Repeat => TextureWrap::Repeat,
+        gltf::texture::WrappingMode::MirroredRepeat => TextureWrap::MirroredRepeat,
+        gltf::texture::WrappingMode::ClampToEdge => TextureWrap::ClampToEdge,
+    }
+}
+
+fn convert_material(material: &gltf::Material, textures: &[RgbaImage]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, a] = pbr.base_color_factor();
+    let diffuse = Color::RGB(float_to_u8(r), float_to_u8(g), float_to_u8(b));
+    let texture = match pbr.base_color_texture() {
+        // The base color texture is multiplied by the base color factor.
+        Some(info) => {
+            let sampler = info.texture().sampler();
+            let mut texture = textures[info.texture().source().index()].clone();
+            // The alpha of the factor is the opacity of the material, which multiplies the
+            // alpha of the texture when drawing.
+            if [r, g, b] != [1.0, 1.0, 1.0] {
+                for pixel in texture.pixels_mut() {
+                    for (channel, factor) in pixel.0.iter_mut().zip([r, g, b]) {
+                        *channel = (*channel as f32 * factor).round() as u8;
+                    }
+                }
+            }
+            Texture::new(texture).with_wrap(
+                convert_wrap(sampler.wrap_s()),
+                convert_wrap(sampler.wrap_t()),
+            )
+        }
+        // Without a texture the material is a solid color, also when drawing with textures.
+        None => Texture::new(solid_texture(diffuse)),
+    };
+    Material {
+        name: material.name().unwrap_or("").to_string(),
+        diffuse,
+        opacity: a,
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => {
+                AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5))
+            }
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        texture: Some(texture),
+        ..Material::default()
+    }
+}
+
+fn convert_image(file_path: &str, image: &gltf::image::Data) -> Result<RgbaImage, MeshLoadError> {
+    use gltf::image::Format;
+
+    let channels = match image.format {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        format => {
+            return Err(gltf_error(
+                file_path,
+                format!("unsupported texture format {:?}", format),
+            ))
+        }
+    };
+    // One and two channel images are gray, the second channel is alpha.
+    let pixels: Vec<u8> = image
+        .pixels
+        .chunks_exact(channels)
+        .flat_map(|p| match channels {
+            1 => [p[0], p[0], p[0], 255],
+            2 => [p[0], p[0], p[0], p[1]],
+            3 => [p[0], p[1], p[2], 255],
+            _ => [p[0], p[1], p[2], p[3]],
+        })
+        .collect();
+    ImageBuffer::from_raw(image.width, image.height, pixels)
+        .ok_or_else(|| gltf_error(file_path, String::from("texture data is too short")))
+}
+
+#[cfg(test)]
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+fn write_triangle_gltf(name: &str, nodes: &str) -> String {
+    // One triangle in the x-y plane facing +z, with positions and normals but no UVs or indices.
+    let mut buffer: Vec<u8> = Vec::new();
+    for f in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+        buffer.extend_from_slice(&f.to_le_bytes());
+    }
+    for _ in 0..3 {
+        for f in [0.0f32, 0.0, 1.0] {
+            buffer.extend_from_slice(&f.to_le_bytes());
+        }
+    }
+    let gltf = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": {nodes},
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }}, "material": 0 }}] }}],
+            "materials": [{{ "name": "red", "pbrMetallicRoughness": {{ "baseColorFactor": [1.0, 0.0, 0.0, 0.5] }}, "alphaMode": "MASK", "alphaCutoff": 0.25 }}],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }}
+            ],
+            "buffers": [{{ "byteLength": 72, "uri": "data:application/octet-stream;base64,{data}" }}]
+        }}"#,
+        nodes = nodes,
+        data = base64_encode(&buffer)
+    );
+    let file_path = std::env::temp_dir().join(name);
+    std::fs::write(&file_path, gltf).unwrap();
+    file_path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_load_gltf_node_transforms() {
+    let file_path = write_triangle_gltf(
+        "test_load_gltf_node_transforms.gltf",
+        r#"[
+            { "translation": [1.0, 2.0, 3.0], "children": [1] },
+            { "scale": [2.0, 2.0, 2.0], "mesh": 0 }
+        ]"#,
+    );
+    let mesh_positions = load_gltf(&file_path).unwrap();
+    std::fs::remove_file(&file_path).unwrap();
+
+    assert_eq!(mesh_positions.len(), 1);
+    let mesh_position = &mesh_positions[0];
+    assert_eq!(mesh_position.position, Vec3::new(1.0, 2.0, 3.0));
+    let mesh = &mesh_position.mesh;
+    assert_eq!(
+        mesh.vertices,
+        vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0)
+        ]
+    );
+    assert_eq!(mesh.normals, vec![Vec3::new(0.0, 0.0, 1.0); 3]);
+    assert_eq!(mesh.uvs, vec![Vec2::new(0.0, 0.0); 3]);
+    assert_eq!(mesh.faces.len(), 1);
+    assert_eq!(mesh.faces[0].material, 1);
+    assert_eq!(mesh.materials.len(), 2);
+    assert_eq!(mesh.materials[1].name, "red");
+    assert_eq!(mesh.materials[1].diffuse, Color::RGB(255, 0, 0));
+    assert_eq!(mesh.materials[1].opacity, 0.5);
+    assert_eq!(mesh.materials[1].alpha_mode, AlphaMode::Mask(0.25));
+}
+
+#[test]
+fn test_load_gltf_mirrored() {
+    let file_path = write_triangle_gltf(
+        "test_load_gltf_mirrored.gltf",
+        r#"[{ "scale": [1.0, 1.0, -1.0], "mesh": 0 }]"#,
+    );
+    let mesh_positions = load_gltf(&file_path).unwrap();
+    std::fs::remove_file(&file_path).unwrap();
+
+    // The normal is mirrored, and the winding is reversed so it still matches the normal.
+    let mesh = &mesh_positions[0].mesh;
+    let face = mesh.faces[0];
+    let (a, b, c) = (
+        mesh.vertices[face.a],
+        mesh.vertices[face.b],
+        mesh.vertices[face.c],
+    );
+    assert_eq!(mesh.normals[face.a], Vec3::new(0.0, 0.0, -1.0));
+    assert!((b - a).cross(c - a).dot(mesh.normals[face.a]) > 0.0);
+}
+
+#[test]
+fn test_load_gltf_cube() {
+    let obj_mesh = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap();
+    let texture = obj_mesh.texture.clone();
+
+    for file_path in ["./assets/cube.gltf", "./assets/cube.glb"] {
+        let mesh_positions = load_gltf(file_path).unwrap();
+        assert_eq!(mesh_positions.len(), 1);
+        let mesh = &mesh_positions[0].mesh;
+        assert_eq!(mesh.faces.len(), obj_mesh.faces.len());
+        // Without a sampler, glTF textures repeat.
+        assert_eq!(
+            mesh.materials[1].texture,
+            Some(
+                texture
+                    .clone()
+                    .with_wrap(TextureWrap::Repeat, TextureWrap::Repeat)
+            )
+        );
+
+        // Each face has the same corners as the OBJ face.
+        for (face, obj_face) in mesh.faces.iter().zip(obj_mesh.faces.iter()) {
+            assert_eq!(mesh.vertices[face.a], obj_mesh.vertices[obj_face.a]);
+            assert_eq!(mesh.vertices[face.b], obj_mesh.vertices[obj_face.b]);
+            assert_eq!(mesh.vertices[face.c], obj_mesh.vertices[obj_face.c]);
+            assert_eq!(mesh.uvs[face.a_uv], obj_mesh.uvs[obj_face.a_uv]);
+            assert_eq!(
+                mesh.normals[face.a_normal],
+                obj_mesh.normals[obj_face.a_normal]
+            );
+        }
+    }
+
+    match load_gltf("./assets/no_such_file.gltf") {
+        Err(MeshLoadError::Gltf { path, .. }) => assert_eq!(path, "./assets/no_such_file.gltf"),
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.len())),
+    }
+}
@@ -1,97 +1,53 @@
+pub mod camera;
+pub mod framebuffer;
+pub mod gltf_import;
+pub mod light;
 pub mod mat;
+pub mod material;
 pub mod mesh;
 pub mod pixel_renderer;
+pub mod ply;
+pub mod quat;
+pub mod shadow;
+pub mod stl;
+pub mod texture;
+pub mod tiles;
 pub mod vec;
 
+use camera::{Camera, Projection};
+use framebuffer::{Framebuffer, RenderTarget};
+use light::{Light, SurfaceLight};
 use mat::Mat4;
-use mesh::Mesh;
-use pixel_renderer::PixelRenderer;
+use material::{AlphaMode, Material};
+use mesh::{Face, Mesh};
+use quat::Quat;
 use rand::{seq::SliceRandom, Rng};
 use sdl2::pixels::Color;
+use shadow::{ShadowMap, Shadows};
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Sub};
+use texture::{Texture, TextureFilter};
 use vec::{Vec2, Vec3, Vec4};
 
 const UP: Vec3 = Vec3::new(0.0, 1.0, 0.0);
-const LIGHT_DIRECTION: Vec3 = Vec3::new(-100.0, 100.0, -50.0);
-const ASPECT_RATIO: f32 = 9.0 / 21.0;
-const FOV: f32 = 60.0; // 60 degrees
-const Z_NEAR: f32 = 0.01;
-const Z_FAR: f32 = 10.0;
-const Z_RATIO: f32 = Z_FAR / (Z_FAR - Z_NEAR);
-
-pub fn project_point_to_camera_space(p: Vec3, camera_location: Vec3, look_at: Vec3) -> Vec3 {
-    camera_view_matrix(camera_location, look_at, UP) * p
-}
-
-pub fn project_point_to_screen_space(screen_width: u32, screen_height: u32, p: Vec3) -> Vec4 {
-    //  f: f32 = 1.732_051;  (1 / (tan(FOV / 2)))
-    let f: f32 = 1.0 / ((FOV.to_radians() / 2.0).tan());
-    let projection_matrix = Mat4::new(
-        // Row 1
-        ASPECT_RATIO * f,
-        0.0,
-        0.0,
-        0.0,
-        // Row 2
-        0.0,
-        -f,
-        0.0,
-        0.0,
-        // Row 3
-        0.0,
-        0.0,
-        Z_RATIO,
-        -Z_RATIO * Z_NEAR,
-        // Row 4
-        0.0,
-        0.0,
-        1.0,
-        0.0,
-    );
 
-    let p = projection_matrix * p.to_vec4();
+// Projects a camera space point onto the screen. x and y of the result are in pixels.
+// z is the depth of the point in camera space, which is used for depth testing.
+// w is what the point was divided by, which is needed to interpolate attributes across a triangle
+// (the distance from the camera with perspective projection, 1.0 with orthographic projection).
+pub fn project_point_to_screen_space(
+    projection_matrix: Mat4,
+    screen_width: u32,
+    screen_height: u32,
+    p: Vec3,
+) -> Vec4 {
+    let clip = projection_matrix * p.to_vec4();
 
     let half_width: f32 = screen_width as f32 / 2.0;
     let half_height: f32 = screen_height as f32 / 2.0;
-    let centered_x = (p.x / p.w) * half_width + half_width;
-    let centered_y = (p.y / p.w) * half_height + half_height;
-    Vec4::new(centered_x, centered_y, p.z, p.w)
-}
-
-pub fn camera_view_matrix(camera_location: Vec3, look_at: Vec3, up: Vec3) -> Mat4 {
-    // Conceptually, we will create a set of orthonormal basis vectors and the Z basis vector will point
-    // towards look_at. Once we have the matrix to transform the standard basis vectors we will find the
-    // inverse (by simply transposing, since it's orthonormal) and apply the inverse to the world.
-    // Applying the inverse to the world will transform the world and place look_at along the standard z axis.
-    // (We will not store the intermediate matrix, we will calculate and return the inverse matrix directly.)
-
-    // The axes that are aligned with look_at, we will call "aligned".
-    let aligned_z_axis = (look_at - camera_location).unit_norm();
-    let aligned_x_axis = up.cross(aligned_z_axis).unit_norm();
-    // When cross arguments are already normal, the result will be normal.
-    let aligned_y_axis = aligned_z_axis.cross(aligned_x_axis);
-
-    Mat4::new(
-        // Row 1
-        aligned_x_axis.x,
-        aligned_x_axis.y,
-        aligned_x_axis.z,
-        -aligned_x_axis.dot(camera_location),
-        // Row 2
-        aligned_y_axis.x,
-        aligned_y_axis.y,
-        aligned_y_axis.z,
-        -aligned_y_axis.dot(camera_location),
-        // Row 3
-        aligned_z_axis.x,
-        aligned_z_axis.y,
-        aligned_z_axis.z,
-        -aligned_z_axis.dot(camera_location),
-        // Row 4
-        0.0,
-        0.0,
-        0.0,
-        1.0,
-    )
+    let centered_x = (clip.x / clip.w) * half_width + half_width;
+    let centered_y = (clip.y / clip.w) * half_height + half_height;
+    Vec4::new(centered_x, centered_y, p.z, clip.w)
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -101,97 +57,173 @@ pub enum TriangleFill {
     Texture,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Shading {
+    // One light intensity per face.
+    Flat,
+    // Light intensity found at each vertex and interpolated across the face.
+    Gouraud,
+    // Normals interpolated across the face and light intensity found at each pixel.
+    Phong,
+}
+
 pub struct DrawOptions {
     pub draw_wireframe: bool,
     pub triangle_fill: TriangleFill,
+    pub shading: Shading,
     pub backface_culling: bool,
     pub pause_rendering: bool,
+    pub shuffle_faces: bool,
+    // Rasterize tiles of the framebuffer in parallel. The image is exactly the same as drawing on
+    // one thread.
+    pub tiled: bool,
+    pub texture_filter: TextureFilter,
+    pub shadows: Shadows,
 }
 
 pub struct MeshPosition {
     pub mesh: Mesh,
     pub position: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    // How fast update_world spins the mesh, in degrees per second. The direction is the axis
+    // of the spin and the length is the speed.
+    pub angular_velocity: Vec3,
+}
+
+impl MeshPosition {
+    pub fn new(mesh: Mesh, position: Vec3) -> Self {
+        Self {
+            mesh,
+            position,
+            rotation: Quat::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            angular_velocity: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn rotation_matrix(&self) -> Mat4 {
+        self.rotation.to_mat4()
+    }
+
+    /// The matrix from model space to world space: scale, then rotate, then translate.
+    pub fn model_matrix(&self) -> Mat4 {
+        Mat4::translate(self.position.x, self.position.y, self.position.z)
+            * self.rotation_matrix()
+            * Mat4::scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
+    /// The matrix for normals from model space to world space.
+    /// Normals stay perpendicular to the surface when transformed by the inverse transpose of the
+    /// model matrix, which is the rotation times the inverse scale. The results need normalizing,
+    /// so the inverse scale is multiplied by |x * y * z| of the scale, which leaves no division
+    /// and keeps the matrix finite when a component of the scale is zero.
+    pub fn normal_matrix(&self) -> Mat4 {
+        let Vec3 { x, y, z } = self.scale;
+        let sign = (x * y * z).signum();
+        self.rotation_matrix() * Mat4::scale(sign * y * z, sign * x * z, sign * x * y)
+    }
 }
 
 pub struct World {
     pub meshes: Vec<MeshPosition>,
-    pub camera_location: Vec3,
-    pub camera_look_at: Vec3,
+    pub camera: Camera,
     pub options: DrawOptions,
+    // The light on each face, vertex or pixel, depending on the shading, is the sum of the lights.
+    pub lights: Vec<Light>,
 }
 
 pub fn update_world_motion(world: &mut World, motion: Vec3, delta_t: f32) {
-    let camera_z = (world.camera_look_at - world.camera_location).unit_norm();
-    let approach_vec = camera_z * motion.z * delta_t;
-    world.camera_location += approach_vec;
-    world.camera_look_at += approach_vec;
-
-    let camera_x = UP.cross(camera_z).unit_norm();
-    let strafe_vec = camera_x * motion.x * delta_t;
-    world.camera_location += strafe_vec;
-    world.camera_look_at += strafe_vec;
-
-    let camera_y = camera_z.cross(camera_x).unit_norm();
-    let fly_vec = camera_y * motion.y * delta_t;
-    world.camera_location += fly_vec;
-    world.camera_look_at += fly_vec;
-}
-
-fn new_basis_matrices(up: Vec3, rot: Vec3) -> (Mat4, Mat4) {
-    let x_axis = rot.unit_norm();
-    let z_axis = x_axis.cross(up).unit_norm();
-    let y_axis = z_axis.cross(x_axis);
-    assert!(y_axis.dot(up) > 0.0);
-
-    let new_basis_mat = Mat4::new(
-        x_axis.x, y_axis.x, z_axis.x, 0.0, // End Row 1
-        x_axis.y, y_axis.y, z_axis.y, 0.0, // End Row 2
-        x_axis.z, y_axis.z, z_axis.z, 0.0, // End Row 3
-        0.0, 0.0, 0.0, 1.0, // End Row 4
-    );
-
-    let inverse_basis_mat = new_basis_mat.transpose();
-
-    (new_basis_mat, inverse_basis_mat)
+    let camera = &mut world.camera;
+    let approach_vec = camera.forward() * motion.z * delta_t;
+    let strafe_vec = camera.right() * motion.x * delta_t;
+    let fly_vec = camera.up() * motion.y * delta_t;
+    camera.location += approach_vec + strafe_vec + fly_vec;
 }
 
 pub fn update_world_rotate(world: &mut World, motion: (i32, i32)) {
-    let look = world.camera_look_at - world.camera_location;
-    let right = UP.cross(look).unit_norm();
-    let (new_basis_mat, inverse_basis_mat) = new_basis_matrices(UP, right);
-
-    let rot_y = Mat4::rotate_y(motion.0 as f32 * 0.03);
-    let rot_x = Mat4::rotate_x(motion.1 as f32 * 0.03);
-    let cam_to_look = world.camera_look_at - world.camera_location;
-    let rotated = rot_y * new_basis_mat * rot_x * inverse_basis_mat * cam_to_look;
-    world.camera_look_at = world.camera_location + rotated;
+    // Turn left and right around the world up axis, so the camera does not roll,
+    // and up and down around the camera's own x axis.
+    let yaw = Quat::from_axis_angle(UP, motion.0 as f32 * 0.03);
+    let pitch = Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), motion.1 as f32 * 0.03);
+    world.camera.orientation = (yaw * world.camera.orientation * pitch).normalize();
 }
 
 pub fn update_world(world: &mut World, delta_t: f32) {
-    for mesh in world.meshes.iter_mut() {
-        let mut rng = rand::thread_rng();
-        if !world.options.pause_rendering && rng.gen::<f32>() < 0.03 {
-            mesh.mesh.rotation.x = mesh.mesh.rotation.x * 0.999 + rng.gen_range(-10.0..10.0);
-            mesh.mesh.rotation.y = mesh.mesh.rotation.y * 0.999 + rng.gen_range(-10.0..10.0);
-            mesh.mesh.rotation.z = mesh.mesh.rotation.z * 0.999 + rng.gen_range(-10.0..10.0);
+    if world.options.pause_rendering {
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    for mesh_position in world.meshes.iter_mut() {
+        let velocity = &mut mesh_position.angular_velocity;
+        if rng.gen::<f32>() < 0.03 {
+            velocity.x = velocity.x * 0.999 + rng.gen_range(-10.0..10.0);
+            velocity.y = velocity.y * 0.999 + rng.gen_range(-10.0..10.0);
+            velocity.z = velocity.z * 0.999 + rng.gen_range(-10.0..10.0);
         }
 
-        if !world.options.pause_rendering {
-            for p in mesh.mesh.vertices.iter_mut() {
-                let rx = Mat4::rotate_x(mesh.mesh.rotation.x * delta_t);
-                let ry = Mat4::rotate_y(mesh.mesh.rotation.y * delta_t);
-                let rz = Mat4::rotate_z(mesh.mesh.rotation.z * delta_t);
-                *p = rx * ry * rz * (*p);
-            }
+        let speed = velocity.len();
+        if speed > 0.0 {
+            // Normalize, so rounding errors do not build up into a scale as rotations are added.
+            let step = Quat::from_axis_angle(*velocity, speed * delta_t);
+            mesh_position.rotation = (step * mesh_position.rotation).normalize();
         }
     }
 }
 
-fn color_mul(color: Color, multiplier: f32) -> Color {
-    let r = (color.r as f32 * multiplier).clamp(0.0, 255.0).round() as u8;
-    let g = (color.g as f32 * multiplier).clamp(0.0, 255.0).round() as u8;
-    let b = (color.b as f32 * multiplier).clamp(0.0, 255.0).round() as u8;
-    Color::RGB(r, g, b)
+fn average_color(colors: [Color; 3]) -> Color {
+    let average = |channel: fn(&Color) -> u8| {
+        (colors.iter().map(|c| channel(c) as u32).sum::<u32>() as f32 / 3.0).round() as u8
+    };
+    Color::RGB(average(|c| c.r), average(|c| c.g), average(|c| c.b))
+}
+
+/// The light reflected by a triangle, as used by the draw_triangle_* functions.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TriangleLight<'a> {
+    // One light for the whole triangle.
+    Flat(SurfaceLight),
+    // The light at each vertex, interpolated across the triangle.
+    Gouraud(SurfaceLight, SurfaceLight, SurfaceLight),
+    // The world space normal and position at each vertex, interpolated across the triangle.
+    // The triangle is shaded per pixel, seen from the eye.
+    Phong {
+        lights: &'a [Light],
+        shadow_maps: &'a [Option<ShadowMap>],
+        material: &'a Material,
+        eye: Vec3,
+        normals: [Vec3; 3],
+        positions: [Vec3; 3],
+    },
+}
+
+impl TriangleLight<'_> {
+    fn surface_light(&self, weights: &PerspectiveWeights) -> SurfaceLight {
+        match *self {
+            TriangleLight::Flat(light) => light,
+            TriangleLight::Gouraud(a, b, c) => SurfaceLight {
+                diffuse: weights.interpolate(a.diffuse, b.diffuse, c.diffuse),
+                specular: weights.interpolate(a.specular, b.specular, c.specular),
+            },
+            TriangleLight::Phong {
+                lights,
+                shadow_maps,
+                material,
+                eye,
+                normals: [normal_a, normal_b, normal_c],
+                positions: [position_a, position_b, position_c],
+            } => light::shade(
+                lights,
+                shadow_maps,
+                material,
+                weights.interpolate(position_a, position_b, position_c),
+                weights
+                    .interpolate(normal_a, normal_b, normal_c)
+                    .unit_norm(),
+                eye,
+            ),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -205,38 +237,71 @@ struct ClipPlane {
 struct ClipVert {
     vert: Vec3,
     uv: Vec2,
+    // The normal and position are in world space, they are only used for lighting.
+    normal: Vec3,
+    position: Vec3,
 }
 
 impl ClipVert {
-    fn new(vert: Vec3, uv: Vec2) -> Self {
-        ClipVert { vert, uv }
-    }
-}
-
-fn frustum_planes() -> Vec<ClipPlane> {
-    // We can't simply multiply angles by the aspect ratio.
-    // We need to convert to triangle ratios and scale one side the the triangle. Time for trig.
-    // The math:
-    // tan(FOV / 2) = h / d; where h is the screen height and d is the camera distance from the screen (d will not matter much)
-    // tan(?) = w / d; we want to find ?
-    // a = h / w
-    // w = h / a
-    // (h / d) / a = w / d
-    // atan(w / d) = ?; this is what we want
-    let vertical_fov_in_degrees = FOV;
-    let horizontal_fov_in_degrees = ((vertical_fov_in_degrees / 2.0).to_radians().tan()
-        / ASPECT_RATIO)
-        .atan()
-        .to_degrees();
+    fn new(vert: Vec3, uv: Vec2, normal: Vec3, position: Vec3) -> Self {
+        ClipVert {
+            vert,
+            uv,
+            normal,
+            position,
+        }
+    }
+}
+
+fn frustum_planes(camera: &Camera, aspect_ratio: f32) -> Vec<ClipPlane> {
+    if camera.projection == Projection::Orthographic {
+        // The view is a box, so the sides are parallel.
+        let half_height = camera.extent / 2.0;
+        let half_width = half_height * aspect_ratio;
+        return vec![
+            ClipPlane {
+                name: "near",
+                point: Vec3::new(0.0, 0.0, camera.z_near),
+                norm: Vec3::new(0.0, 0.0, 1.0),
+            },
+            ClipPlane {
+                name: "far",
+                point: Vec3::new(0.0, 0.0, camera.z_far),
+                norm: Vec3::new(0.0, 0.0, -1.0),
+            },
+            ClipPlane {
+                name: "left",
+                point: Vec3::new(-half_width, 0.0, 0.0),
+                norm: Vec3::new(1.0, 0.0, 0.0),
+            },
+            ClipPlane {
+                name: "right",
+                point: Vec3::new(half_width, 0.0, 0.0),
+                norm: Vec3::new(-1.0, 0.0, 0.0),
+            },
+            ClipPlane {
+                name: "top",
+                point: Vec3::new(0.0, half_height, 0.0),
+                norm: Vec3::new(0.0, -1.0, 0.0),
+            },
+            ClipPlane {
+                name: "bottom",
+                point: Vec3::new(0.0, -half_height, 0.0),
+                norm: Vec3::new(0.0, 1.0, 0.0),
+            },
+        ];
+    }
+
+    let horizontal_fov_in_degrees = camera.horizontal_fov(aspect_ratio) / 2.0;
     vec![
         ClipPlane {
             name: "near",
-            point: Vec3::new(0.0, 0.0, Z_NEAR),
+            point: Vec3::new(0.0, 0.0, camera.z_near),
             norm: Vec3::new(0.0, 0.0, 1.0),
         },
         ClipPlane {
             name: "far",
-            point: Vec3::new(0.0, 0.0, Z_FAR),
+            point: Vec3::new(0.0, 0.0, camera.z_far),
             norm: Vec3::new(0.0, 0.0, -1.0),
         },
         ClipPlane {
@@ -252,12 +317,12 @@ fn frustum_planes() -> Vec<ClipPlane> {
         ClipPlane {
             name: "top",
             point: Vec3::new(0.0, 0.0, 0.0),
-            norm: Mat4::rotate_x(-FOV / 2.0) * Vec3::new(0.0, -1.0, 0.0),
+            norm: Mat4::rotate_x(-camera.fov / 2.0) * Vec3::new(0.0, -1.0, 0.0),
         },
         ClipPlane {
             name: "bottom",
             point: Vec3::new(0.0, 0.0, 0.0),
-            norm: Mat4::rotate_x(FOV / 2.0) * Vec3::new(0.0, 1.0, 0.0),
+            norm: Mat4::rotate_x(camera.fov / 2.0) * Vec3::new(0.0, 1.0, 0.0),
         },
     ]
 }
@@ -279,10 +344,14 @@ fn intersection(v0: ClipVert, v1: ClipVert, plane: &ClipPlane) -> ClipVert {
     let v0_weight = v1_dist_from_intersect / line_segment_len;
     let v1_weight = v0_dist_from_intersect / line_segment_len;
     let intersect_uv = v0.uv * v0_weight + v1.uv * v1_weight;
+    let intersect_normal = v0.normal * v0_weight + v1.normal * v1_weight;
+    let intersect_position = v0.position * v0_weight + v1.position * v1_weight;
 
     ClipVert {
         vert: intersect,
         uv: intersect_uv,
+        normal: intersect_normal,
+        position: intersect_position,
     }
 }
 
@@ -316,51 +385,296 @@ fn frustum_clip(poly: &mut Vec<ClipVert>, clip_planes: &[ClipPlane]) {
     }
 }
 
-pub fn draw_meshes(pixel_renderer: &mut PixelRenderer, world: &World) {
-    pixel_renderer.clear_pixels(Color::RGB(0, 0, 0));
+pub fn draw_meshes(framebuffer: &mut Framebuffer, world: &World) {
+    framebuffer.clear_pixels(Color::RGB(0, 0, 0));
 
+    let shadow_maps = shadow::render_shadow_maps(world);
+    let view = View::of_world(world, &shadow_maps);
+    let mut commands = Vec::new();
     for mesh_position in world.meshes.iter() {
-        draw_mesh(pixel_renderer, world, mesh_position);
+        push_mesh_draw_commands(
+            &mut commands,
+            framebuffer.width,
+            framebuffer.height,
+            &view,
+            mesh_position,
+        );
     }
+    sort_blended_last(&mut commands);
+    run_draw_commands(framebuffer, &commands, world.options.tiled);
 }
 
-pub fn draw_mesh(pixel_renderer: &mut PixelRenderer, world: &World, mesh_position: &MeshPosition) {
-    let mut rng = rand::thread_rng();
-    let mesh: &Mesh = &mesh_position.mesh;
-    let draw_options = &world.options;
+// Draw one mesh of the world. The shadow maps are from shadow::render_shadow_maps(world), so other
+// meshes in the world still cast shadows on this one. They cost a pass over the whole world for
+// each light, so render them once per frame and share them between the meshes drawn in it.
+pub fn draw_mesh(
+    framebuffer: &mut Framebuffer,
+    world: &World,
+    shadow_maps: &[Option<ShadowMap>],
+    mesh_position: &MeshPosition,
+) {
+    let view = View::of_world(world, shadow_maps);
+    let mut commands = Vec::new();
+    push_mesh_draw_commands(
+        &mut commands,
+        framebuffer.width,
+        framebuffer.height,
+        &view,
+        mesh_position,
+    );
+    sort_blended_last(&mut commands);
+    run_draw_commands(framebuffer, &commands, world.options.tiled);
+}
+
+fn run_draw_commands(framebuffer: &mut Framebuffer, commands: &[DrawCommand], tiled: bool) {
+    if tiled {
+        tiles::draw_tiled(framebuffer, commands);
+    } else {
+        for command in commands {
+            command.draw(framebuffer);
+        }
+    }
+}
 
-    let clip_planes = frustum_planes();
+// Something to draw into the framebuffer, in screen space. Meshes are turned into a list of
+// these, which can be drawn in order on one thread, or split up by tiles::draw_tiled.
+#[derive(Debug, Clone, Copy)]
+pub enum DrawCommand<'a> {
+    // The alpha of the color is the opacity.
+    TriangleColor {
+        color: Color,
+        alpha_mode: AlphaMode,
+        light: TriangleLight<'a>,
+        vertices: [Vec4; 3],
+    },
+    TriangleTexture {
+        texture: &'a Texture,
+        filter: TextureFilter,
+        opacity: f32,
+        alpha_mode: AlphaMode,
+        light: TriangleLight<'a>,
+        vertices: [Vec4; 3],
+        uvs: [Vec2; 3],
+    },
+    Line {
+        color: Color,
+        a: Vec4,
+        b: Vec4,
+    },
+    Point {
+        x: u32,
+        y: u32,
+        color: Color,
+    },
+}
 
-    'faces: for face in mesh.faces.choose_multiple(&mut rng, mesh.faces.len()) {
-        let vert_a = mesh.vertices[face.a] + mesh_position.position;
-        let vert_b = mesh.vertices[face.b] + mesh_position.position;
-        let vert_c = mesh.vertices[face.c] + mesh_position.position;
+impl DrawCommand<'_> {
+    pub fn draw(&self, target: &mut impl RenderTarget) {
+        match *self {
+            DrawCommand::TriangleColor {
+                color,
+                alpha_mode,
+                light,
+                vertices: [a, b, c],
+            } => draw_triangle_color(target, color, alpha_mode, light, a, b, c),
+            DrawCommand::TriangleTexture {
+                texture,
+                filter,
+                opacity,
+                alpha_mode,
+                light,
+                vertices: [a, b, c],
+                uvs: [a_uv, b_uv, c_uv],
+            } => draw_triangle_texture(
+                target, texture, filter, opacity, alpha_mode, light, a, b, c, a_uv, b_uv, c_uv,
+            ),
+            DrawCommand::Line { color, a, b } => draw_line(target, color, a, b),
+            DrawCommand::Point { x, y, color } => target.set_pixel(x, y, color),
+        }
+    }
+
+    /// The pixels the command may draw to, as (x_min, y_min, x_max, y_max), inclusive.
+    /// The bounds can reach outside the screen.
+    pub fn bounds(&self) -> (i32, i32, i32, i32) {
+        let unbounded = (i32::MIN, i32::MIN, i32::MAX, i32::MAX);
+        match *self {
+            DrawCommand::TriangleColor {
+                vertices: [a, b, c],
+                ..
+            }
+            | DrawCommand::TriangleTexture {
+                vertices: [a, b, c],
+                ..
+            } => triangle_bounds(a, b, c, unbounded),
+            // draw_line only steps between the rounded end points.
+            DrawCommand::Line { a, b, .. } => {
+                let (a_x, a_y) = (a.x.round() as i32, a.y.round() as i32);
+                let (b_x, b_y) = (b.x.round() as i32, b.y.round() as i32);
+                (a_x.min(b_x), a_y.min(b_y), a_x.max(b_x), a_y.max(b_y))
+            }
+            DrawCommand::Point { x, y, .. } => {
+                let x = x.min(i32::MAX as u32) as i32;
+                let y = y.min(i32::MAX as u32) as i32;
+                (x, y, x, y)
+            }
+        }
+    }
+
+    /// The average camera depth of a blended triangle, or None for anything drawn opaque.
+    pub fn blended_depth(&self) -> Option<f32> {
+        match *self {
+            DrawCommand::TriangleColor {
+                alpha_mode: AlphaMode::Blend,
+                vertices: [a, b, c],
+                ..
+            }
+            | DrawCommand::TriangleTexture {
+                alpha_mode: AlphaMode::Blend,
+                vertices: [a, b, c],
+                ..
+            } => Some((a.z + b.z + c.z) / 3.0),
+            _ => None,
+        }
+    }
+}
+
+// Blended triangles do not write depth, so they are drawn after everything else, furthest first,
+// for each to blend over everything behind it. The order of everything else is kept.
+pub fn sort_blended_last(commands: &mut [DrawCommand]) {
+    commands.sort_by(|a, b| match (a.blended_depth(), b.blended_depth()) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(a), Some(b)) => b.total_cmp(&a),
+    });
+}
+
+// What meshes are drawn from and with. This is usually the camera and options of the world, but
+// shadow maps are drawn from the lights.
+#[derive(Clone, Copy)]
+pub struct View<'a> {
+    pub camera: &'a Camera,
+    pub options: &'a DrawOptions,
+    pub lights: &'a [Light],
+    // The shadow map of each light, if it has one.
+    pub shadow_maps: &'a [Option<ShadowMap>],
+}
+
+impl<'a> View<'a> {
+    pub fn of_world(world: &'a World, shadow_maps: &'a [Option<ShadowMap>]) -> Self {
+        Self {
+            camera: &world.camera,
+            options: &world.options,
+            lights: &world.lights,
+            shadow_maps,
+        }
+    }
+}
+
+// Transform, cull, clip, light and project the faces of a mesh, and push the commands that draw
+// them. width and height are the size of the framebuffer they will be drawn into.
+pub fn push_mesh_draw_commands<'a>(
+    commands: &mut Vec<DrawCommand<'a>>,
+    width: u32,
+    height: u32,
+    view: &View<'a>,
+    mesh_position: &'a MeshPosition,
+) {
+    let mut rng = rand::thread_rng();
+    let mesh: &Mesh = &mesh_position.mesh;
+    let draw_options = view.options;
+    let camera = view.camera;
+
+    // The aspect ratio comes from the framebuffer, so the image is not stretched at any size.
+    let aspect_ratio = width as f32 / height as f32;
+    let clip_planes = frustum_planes(camera, aspect_ratio);
+    let view_matrix = camera.view_matrix();
+    let projection_matrix = camera.projection_matrix(aspect_ratio);
+
+    // The mesh itself is never modified. Its vertices and normals are moved into world space here.
+    let model_matrix = mesh_position.model_matrix();
+    let normal_matrix = mesh_position.normal_matrix();
+    let vertices: Vec<Vec3> = mesh.vertices.iter().map(|&v| model_matrix * v).collect();
+    let normals: Vec<Vec3> = mesh
+        .normals
+        .iter()
+        .map(|&n| (normal_matrix * n).unit_norm())
+        .collect();
+    // A negative scale mirrors the mesh, which reverses the winding order of its faces.
+    let scale = mesh_position.scale;
+    let mirrored = scale.x * scale.y * scale.z < 0.0;
+
+    // Drawing the faces in a random order shows z-buffer problems that a fixed order might hide.
+    // A fixed order makes the output reproducible, which tests need.
+    let faces: Vec<&Face> = if draw_options.shuffle_faces {
+        mesh.faces
+            .choose_multiple(&mut rng, mesh.faces.len())
+            .collect()
+    } else {
+        mesh.faces.iter().collect()
+    };
+
+    'faces: for face in faces {
+        let (face_b, face_c, b_uv, c_uv, b_normal, c_normal) = if mirrored {
+            (
+                face.c,
+                face.b,
+                face.c_uv,
+                face.b_uv,
+                face.c_normal,
+                face.b_normal,
+            )
+        } else {
+            (
+                face.b,
+                face.c,
+                face.b_uv,
+                face.c_uv,
+                face.b_normal,
+                face.c_normal,
+            )
+        };
+        let vert_a = vertices[face.a];
+        let vert_b = vertices[face_b];
+        let vert_c = vertices[face_c];
 
         let face_normal = (vert_b - vert_a).cross(vert_c - vert_a).unit_norm();
         if draw_options.backface_culling {
-            let vec_to_camera = world.camera_location - vert_a;
+            // With orthographic projection all faces are seen from the same direction.
+            let vec_to_camera = match camera.projection {
+                Projection::Perspective => camera.location - vert_a,
+                Projection::Orthographic => -camera.forward(),
+            };
             if face_normal.dot(vec_to_camera) <= 0.0 {
                 continue 'faces;
             }
         }
 
         let uv_a = mesh.uvs[face.a_uv];
-        let uv_b = mesh.uvs[face.b_uv];
-        let uv_c = mesh.uvs[face.c_uv];
+        let uv_b = mesh.uvs[b_uv];
+        let uv_c = mesh.uvs[c_uv];
+
+        let material = &mesh.materials[face.material];
+        let texture = material.texture.as_ref().unwrap_or(&mesh.texture);
+        // Meshes with vertex colors are filled with the average color of the face.
+        let color = if mesh.colors.is_empty() {
+            material.diffuse
+        } else {
+            average_color([
+                mesh.colors[face.a],
+                mesh.colors[face_b],
+                mesh.colors[face_c],
+            ])
+        };
+
+        let normal_a = normals[face.a_normal];
+        let normal_b = normals[b_normal];
+        let normal_c = normals[c_normal];
 
         let mut polygons = Vec::with_capacity(10);
-        polygons.push(ClipVert::new(
-            project_point_to_camera_space(vert_a, world.camera_location, world.camera_look_at),
-            uv_a,
-        ));
-        polygons.push(ClipVert::new(
-            project_point_to_camera_space(vert_b, world.camera_location, world.camera_look_at),
-            uv_b,
-        ));
-        polygons.push(ClipVert::new(
-            project_point_to_camera_space(vert_c, world.camera_location, world.camera_look_at),
-            uv_c,
-        ));
+        polygons.push(ClipVert::new(view_matrix * vert_a, uv_a, normal_a, vert_a));
+        polygons.push(ClipVert::new(view_matrix * vert_b, uv_b, normal_b, vert_b));
+        polygons.push(ClipVert::new(view_matrix * vert_c, uv_c, normal_c, vert_c));
 
         frustum_clip(&mut polygons, &clip_planes);
 
@@ -379,194 +693,429 @@ pub fn draw_mesh(pixel_renderer: &mut PixelRenderer, world: &World, mesh_positio
             let uv_b = polygons[i].uv;
             let uv_c = polygons[i + 1].uv;
 
-            let is_facing_light = face_normal.dot(LIGHT_DIRECTION.unit_norm());
-            let (intensity_min, intensity_max) = (0.4, 1.2);
-            let light_intensity = ((is_facing_light + 1.0) / (1.0 + 1.0))
-                * (intensity_max - intensity_min)
-                + intensity_min;
+            let normal_a = polygons[0].normal;
+            let normal_b = polygons[i].normal;
+            let normal_c = polygons[i + 1].normal;
+
+            let position_a = polygons[0].position;
+            let position_b = polygons[i].position;
+            let position_c = polygons[i + 1].position;
+
+            let (lights, shadow_maps) = (view.lights, view.shadow_maps);
+            let eye = camera.location;
+            let shade = |position: Vec3, normal: Vec3| {
+                light::shade(
+                    lights,
+                    shadow_maps,
+                    material,
+                    position,
+                    normal.unit_norm(),
+                    eye,
+                )
+            };
+            let light = match draw_options.shading {
+                // Lit at the centre of the whole face, so the pieces of a clipped face match.
+                Shading::Flat => {
+                    let centre = (vertices[face.a] + vertices[face_b] + vertices[face_c]) / 3.0;
+                    TriangleLight::Flat(shade(centre, face_normal))
+                }
+                Shading::Gouraud => TriangleLight::Gouraud(
+                    shade(position_a, normal_a),
+                    shade(position_b, normal_b),
+                    shade(position_c, normal_c),
+                ),
+                Shading::Phong => TriangleLight::Phong {
+                    lights,
+                    shadow_maps,
+                    material,
+                    eye,
+                    normals: [normal_a, normal_b, normal_c],
+                    positions: [position_a, position_b, position_c],
+                },
+            };
 
-            let pa =
-                project_point_to_screen_space(pixel_renderer.width, pixel_renderer.height, vert_a);
-            let pb =
-                project_point_to_screen_space(pixel_renderer.width, pixel_renderer.height, vert_b);
-            let pc =
-                project_point_to_screen_space(pixel_renderer.width, pixel_renderer.height, vert_c);
+            let pa = project_point_to_screen_space(projection_matrix, width, height, vert_a);
+            let pb = project_point_to_screen_space(projection_matrix, width, height, vert_b);
+            let pc = project_point_to_screen_space(projection_matrix, width, height, vert_c);
 
             if draw_options.triangle_fill == TriangleFill::Color {
-                draw_triangle_color(
-                    pixel_renderer,
-                    color_mul(face.color, light_intensity),
-                    pa,
-                    pb,
-                    pc,
-                );
+                commands.push(DrawCommand::TriangleColor {
+                    color: Color {
+                        a: (material.opacity * 255.0).clamp(0.0, 255.0).round() as u8,
+                        ..color
+                    },
+                    alpha_mode: material.alpha_mode,
+                    light,
+                    vertices: [pa, pb, pc],
+                });
             } else if draw_options.triangle_fill == TriangleFill::Texture {
-                draw_triangle_texture(
-                    pixel_renderer,
-                    mesh,
-                    light_intensity,
-                    pa,
-                    pb,
-                    pc,
-                    uv_a,
-                    uv_b,
-                    uv_c,
-                );
+                commands.push(DrawCommand::TriangleTexture {
+                    texture,
+                    filter: draw_options.texture_filter,
+                    opacity: material.opacity,
+                    alpha_mode: material.alpha_mode,
+                    light,
+                    vertices: [pa, pb, pc],
+                    uvs: [uv_a, uv_b, uv_c],
+                });
             }
 
             if draw_options.draw_wireframe {
-                draw_line(pixel_renderer, Color::RGB(255, 255, 255), pa, pb);
-                draw_line(pixel_renderer, Color::RGB(255, 255, 255), pb, pc);
-                draw_line(pixel_renderer, Color::RGB(255, 255, 255), pc, pa);
+                let color = Color::RGB(255, 255, 255);
+                commands.push(DrawCommand::Line {
+                    color,
+                    a: pa,
+                    b: pb,
+                });
+                commands.push(DrawCommand::Line {
+                    color,
+                    a: pb,
+                    b: pc,
+                });
+                commands.push(DrawCommand::Line {
+                    color,
+                    a: pc,
+                    b: pa,
+                });
             }
 
             if draw_options.triangle_fill == TriangleFill::None && !draw_options.draw_wireframe {
-                pixel_renderer.set_pixel(
-                    pa.x.round() as u32,
-                    pa.y.round() as u32,
-                    Color::RGB(255, 255, 255),
-                );
-                pixel_renderer.set_pixel(
-                    pb.x.round() as u32,
-                    pb.y.round() as u32,
-                    Color::RGB(255, 255, 255),
-                );
-                pixel_renderer.set_pixel(
-                    pc.x.round() as u32,
-                    pc.y.round() as u32,
-                    Color::RGB(255, 255, 255),
-                );
+                for p in [pa, pb, pc] {
+                    commands.push(DrawCommand::Point {
+                        x: p.x.round() as u32,
+                        y: p.y.round() as u32,
+                        color: Color::RGB(255, 255, 255),
+                    });
+                }
             }
         }
 
-        pixel_renderer.set_pixel(0, 0, Color::RGB(255, 255, 255));
-        pixel_renderer.set_pixel(10, 0, Color::RGB(255, 0, 0));
-        pixel_renderer.set_pixel(0, 10, Color::RGB(0, 255, 0));
-        pixel_renderer.set_pixel(10, 10, Color::RGB(0, 0, 255));
+        for (x, y, color) in [
+            (0, 0, Color::RGB(255, 255, 255)),
+            (10, 0, Color::RGB(255, 0, 0)),
+            (0, 10, Color::RGB(0, 255, 0)),
+            (10, 10, Color::RGB(0, 0, 255)),
+        ] {
+            commands.push(DrawCommand::Point { x, y, color });
+        }
     }
 }
 
-fn min_max(a: f32, b: f32, c: f32) -> (i32, i32) {
-    let min;
-    let max;
+// Screen space vertices are snapped to a grid of 1 / SUBPIXEL_SCALE of a pixel before rasterizing,
+// so coverage is decided with exact integer arithmetic. Triangles sharing an edge see exactly the
+// same edge, and every pixel centre on it belongs to exactly one of them.
+pub const SUBPIXEL_BITS: u32 = 8;
+pub const SUBPIXEL_SCALE: i64 = 1 << SUBPIXEL_BITS;
 
-    if a <= b && a <= c {
-        min = a;
-    } else if b <= c {
-        min = b;
-    } else {
-        min = c;
-    }
-
-    if a >= b && a >= c {
-        max = a;
-    } else if b >= c {
-        max = b;
-    } else {
-        max = c;
-    }
+// Snapped coordinates are kept within this many sub-pixels of the origin, so products of
+// coordinate differences fit in an i64. That is a million pixels, far outside any framebuffer.
+const SUBPIXEL_LIMIT: i64 = 1 << 28;
 
-    (min.round() as i32, max.round() as i32)
+// A screen space point in sub-pixels.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct SubPixel {
+    x: i64,
+    y: i64,
 }
 
-fn cross_edge(p: Vec2, vert: Vec2, edge_from_vert: Vec2) -> f32 {
-    let vert_to_p = p - vert;
-    let mut cross_z = edge_from_vert.cross_z(vert_to_p);
-    if edge_from_vert.y > 0.0 || (edge_from_vert.y == 0.0 && edge_from_vert.x > 0.0) {
-        cross_z += 1.0;
+impl SubPixel {
+    fn snap(v: Vec4) -> Self {
+        let snap = |v: f32| {
+            ((v * SUBPIXEL_SCALE as f32).round() as i64).clamp(-SUBPIXEL_LIMIT, SUBPIXEL_LIMIT)
+        };
+        SubPixel {
+            x: snap(v.x),
+            y: snap(v.y),
+        }
+    }
+
+    // The sample position of a pixel, its centre.
+    fn pixel_centre(x: i32, y: i32) -> Self {
+        SubPixel {
+            x: x as i64 * SUBPIXEL_SCALE + SUBPIXEL_SCALE / 2,
+            y: y as i64 * SUBPIXEL_SCALE + SUBPIXEL_SCALE / 2,
+        }
     }
-    cross_z.signum()
 }
 
-pub fn draw_triangle_color(
-    pixel_renderer: &mut PixelRenderer,
-    color: Color,
+// The pixels whose centres are within the bounding box of a screen space triangle, clamped to the
+// bounds of a render target.
+fn triangle_bounds(
     a: Vec4,
     b: Vec4,
     c: Vec4,
-) {
-    let (x_min, x_max): (i32, i32) = min_max(a.x, b.x, c.x);
-    let (y_min, y_max): (i32, i32) = min_max(a.y, b.y, c.y);
-
-    let x_min = x_min.max(0);
-    let y_min = y_min.max(0);
-
-    let edge_from_a = b - a;
-    let edge_from_b = c - b;
-    let edge_from_c = a - c;
-
-    for x in x_min..=x_max {
-        for y in y_min..=y_max {
-            let p = Vec2::new(x as f32, y as f32);
-            let in_a = cross_edge(p, a.to_vec2(), edge_from_a.to_vec2());
-            let in_b = cross_edge(p, b.to_vec2(), edge_from_b.to_vec2());
-            let in_c = cross_edge(p, c.to_vec2(), edge_from_c.to_vec2());
-
-            if in_a == in_b && in_a == in_c {
-                let p0 = Vec2::new(0.0, 0.0);
-                let (_uv, w) = interpolate_uv(Vec2::new(x as f32, y as f32), a, b, c, p0, p0, p0);
-                pixel_renderer.set_pixel_z(x as u32, y as u32, w, color);
-            }
+    (x_low, y_low, x_high, y_high): (i32, i32, i32, i32),
+) -> (i32, i32, i32, i32) {
+    let [a, b, c] = [a, b, c].map(SubPixel::snap);
+    let half = SUBPIXEL_SCALE / 2;
+    // The first pixel with its centre at or after the minimum, and the last pixel with its centre
+    // at or before the maximum.
+    let first = |min: i64| (min - half + SUBPIXEL_SCALE - 1).div_euclid(SUBPIXEL_SCALE);
+    let last = |max: i64| (max - half).div_euclid(SUBPIXEL_SCALE);
+    (
+        first(a.x.min(b.x).min(c.x)).max(x_low as i64) as i32,
+        first(a.y.min(b.y).min(c.y)).max(y_low as i64) as i32,
+        last(a.x.max(b.x).max(c.x)).min(x_high as i64) as i32,
+        last(a.y.max(b.y).max(c.y)).min(y_high as i64) as i32,
+    )
+}
+
+// Triangles are rasterized in square blocks of pixels aligned to a grid, so that blocks entirely
+// inside or outside a triangle can be accepted or rejected without testing each pixel.
+pub const RASTER_BLOCK_SIZE: i32 = 8;
+
+// The edge function of an edge, stepped from pixel centre to pixel centre.
+// Its value at a point is edge_from_vert.cross_z(p - vert) in sub-pixels, made positive on the
+// inside of the triangle.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct EdgeFunction {
+    vert: SubPixel,
+    edge_from_vert: SubPixel,
+    // The smallest value of a pixel centre that is inside: 0 on a top or left edge, so pixel
+    // centres exactly on the edge are inside, and 1 on other edges, so they are outside.
+    min_inside: i64,
+}
+
+impl EdgeFunction {
+    fn new(vert: SubPixel, to: SubPixel) -> Self {
+        let edge = SubPixel {
+            x: to.x - vert.x,
+            y: to.y - vert.y,
+        };
+        // Screen space y points down, and the inside of a triangle is to the right of its edges
+        // when walking around it clockwise on screen. A top edge is horizontal with the inside
+        // below it, and a left edge goes up.
+        let top = edge.y == 0 && edge.x > 0;
+        let left = edge.y < 0;
+        EdgeFunction {
+            vert,
+            edge_from_vert: edge,
+            min_inside: if top || left { 0 } else { 1 },
         }
     }
+
+    fn at(&self, p: SubPixel) -> i64 {
+        self.edge_from_vert.x * (p.y - self.vert.y) - self.edge_from_vert.y * (p.x - self.vert.x)
+    }
+
+    // The change in value from one pixel centre to the next one to the right.
+    fn step_x(&self) -> i64 {
+        -self.edge_from_vert.y * SUBPIXEL_SCALE
+    }
+
+    // The change in value from one pixel centre to the next one down.
+    fn step_y(&self) -> i64 {
+        self.edge_from_vert.x * SUBPIXEL_SCALE
+    }
 }
 
-fn interpolate_uv(
-    p: Vec2,
+// Calls draw_pixel with the perspective correct weights of every pixel covered by a screen space
+// triangle, within the bounds of a render target.
+//
+// A pixel is covered when its centre is inside the triangle. Centres exactly on an edge belong to
+// the triangle on its bottom or right side (the edge is the top or left of that triangle), so
+// triangles sharing an edge cover each pixel along it exactly once.
+fn rasterize_triangle(
+    bounds: (i32, i32, i32, i32),
     a4: Vec4,
     b4: Vec4,
     c4: Vec4,
-    a_uv: Vec2,
-    b_uv: Vec2,
-    c_uv: Vec2,
-) -> (Vec2, f32) {
-    let a = a4.to_vec2();
-    let b = b4.to_vec2();
-    let c = c4.to_vec2();
-    let mut a_weight = ((p - b).cross_z(p - c)) / ((a - b).cross_z(a - c));
-    let mut b_weight = ((p - a).cross_z(p - c)) / ((b - a).cross_z(b - c));
-    let mut c_weight = ((p - a).cross_z(p - b)) / ((c - a).cross_z(c - b));
-
-    // debug_assert!((-0.1..1.1).contains(&a_weight));
-    // debug_assert!((-0.1..1.1).contains(&b_weight));
-    // debug_assert!((-0.1..1.1).contains(&c_weight));
-    // debug_assert!((0.9..1.1).contains(&(a_weight + b_weight + c_weight)));
-
-    // At this point in the code, the weights are correct for screen space.
-    // These weights can be used in a linear combination of the screen space UV coordinates.
-    // A linear combination in screen space is not what we want though, we want a world space combination.
-    // See: https://www.comp.nus.edu.sg/~lowkl/publications/lowk_persp_interp_techrep.pdf
+    mut draw_pixel: impl FnMut(u32, u32, &PerspectiveWeights),
+) {
+    let (x_min, y_min, x_max, y_max) = triangle_bounds(a4, b4, c4, bounds);
+    if x_min > x_max || y_min > y_max {
+        return;
+    }
+
+    let a = SubPixel::snap(a4);
+    let b = SubPixel::snap(b4);
+    let c = SubPixel::snap(c4);
 
+    // Twice the signed area of the triangle, positive when it is clockwise on screen. A triangle
+    // without area covers no pixels, and the edges of a counterclockwise triangle are walked the
+    // other way.
+    let area = EdgeFunction::new(b, c).at(a);
+    if area == 0 {
+        return;
+    }
+    // Edges opposite a, b and c.
+    let edges = if area > 0 {
+        [
+            EdgeFunction::new(b, c),
+            EdgeFunction::new(c, a),
+            EdgeFunction::new(a, b),
+        ]
+    } else {
+        [
+            EdgeFunction::new(c, b),
+            EdgeFunction::new(a, c),
+            EdgeFunction::new(b, a),
+        ]
+    };
+    let area = area.abs();
+
+    // The screen space weight of a vertex is the edge function of the opposite edge, divided by its
+    // value at the vertex, which is the area. These weights can be used in a linear combination of
+    // the screen space UV coordinates. A linear combination in screen space is not what we want
+    // though, we want a world space combination.
+    // See: https://www.comp.nus.edu.sg/~lowkl/publications/lowk_persp_interp_techrep.pdf
+    //
     // The world space coordinates were divided by their depth when projecting to screen space;
-    // this division is not a linear transformation and must be specially accounted for.
-    // We also need to divide the weights by the depth of their associated vertex. We do so below:
+    // this division is not a linear transformation and must be specially accounted for. We also
+    // divide the weights by the depth of their associated vertex. Dividing by the sum of the
+    // weights renormalizes them to a 0 to 1 range again, which happens in interpolate.
+    let weight_scale = [a4.w, b4.w, c4.w].map(|w| 1.0 / (area as f32 * w));
+
+    let step_x = edges.map(|edge| edge.step_x());
+    let step_y = edges.map(|edge| edge.step_y());
+    let weight_step = |step: [i64; 3]| {
+        Vec3::new(
+            step[0] as f32 * weight_scale[0],
+            step[1] as f32 * weight_scale[1],
+            step[2] as f32 * weight_scale[2],
+        )
+    };
+    let (weight_step_x, weight_step_y) = (weight_step(step_x), weight_step(step_y));
+    let min_inside = edges.map(|edge| edge.min_inside);
+    let inside = |e: [i64; 3]| (0..3).all(|i| e[i] >= min_inside[i]);
+
+    // Blocks are aligned to a grid of RASTER_BLOCK_SIZE anchored at the origin, rather than to the
+    // bounds, so a pixel gets exactly the same values whichever render target it is drawn into.
+    let block_x_min = x_min - x_min.rem_euclid(RASTER_BLOCK_SIZE);
+    let block_y_min = y_min - y_min.rem_euclid(RASTER_BLOCK_SIZE);
+    let last = (RASTER_BLOCK_SIZE - 1) as i64;
+
+    for block_y in (block_y_min..=y_max).step_by(RASTER_BLOCK_SIZE as usize) {
+        for block_x in (block_x_min..=x_max).step_by(RASTER_BLOCK_SIZE as usize) {
+            let mut edge_row = edges.map(|edge| edge.at(SubPixel::pixel_centre(block_x, block_y)));
+
+            // The edge functions are linear, so their extremes over the block are at the corners.
+            let corners = [[0, 0], [last, 0], [0, last], [last, last]]
+                .map(|[x, y]| [0, 1, 2].map(|i| edge_row[i] + step_x[i] * x + step_y[i] * y));
+            if (0..3).any(|i| corners.iter().all(|e| e[i] < min_inside[i])) {
+                continue;
+            }
+            let accept = corners.iter().all(|&e| inside(e));
+
+            for y in block_y..block_y + RASTER_BLOCK_SIZE {
+                let mut e = edge_row;
+                for x in block_x..block_x + RASTER_BLOCK_SIZE {
+                    let in_bounds = x >= x_min && x <= x_max && y >= y_min && y <= y_max;
+                    if in_bounds && (accept || inside(e)) {
+                        let a = e[0] as f32 * weight_scale[0];
+                        let b = e[1] as f32 * weight_scale[1];
+                        let c = e[2] as f32 * weight_scale[2];
+                        let weights = PerspectiveWeights {
+                            a,
+                            b,
+                            c,
+                            sum: a + b + c,
+                            step_x: weight_step_x,
+                            step_y: weight_step_y,
+                        };
+                        draw_pixel(x as u32, y as u32, &weights);
+                    }
+                    for i in 0..3 {
+                        e[i] += step_x[i];
+                    }
+                }
+                for i in 0..3 {
+                    edge_row[i] += step_y[i];
+                }
+            }
+        }
+    }
+}
 
-    a_weight /= a4.w;
-    b_weight /= b4.w;
-    c_weight /= c4.w;
+// Draw a pixel of a triangle by its alpha mode.
+fn set_triangle_pixel(
+    target: &mut impl RenderTarget,
+    x: u32,
+    y: u32,
+    z: f32,
+    color: Color,
+    alpha_mode: AlphaMode,
+) {
+    let opaque = Color::RGB(color.r, color.g, color.b);
+    match alpha_mode {
+        AlphaMode::Opaque => target.set_pixel_z(x, y, z, opaque),
+        AlphaMode::Mask(cutoff) => {
+            if color.a as f32 >= cutoff * 255.0 {
+                target.set_pixel_z(x, y, z, opaque);
+            }
+        }
+        AlphaMode::Blend => target.blend_pixel_z(x, y, z, color),
+    }
+}
 
-    // At this point in the code, the world space coordinates have been divided by depth
-    // as part of the projection to screen space, and their associated weights have also been divided by depth.
-    // We are ready to use the weights to combine the UV coordinates.
+pub fn draw_triangle_color(
+    target: &mut impl RenderTarget,
+    color: Color,
+    alpha_mode: AlphaMode,
+    light: TriangleLight,
+    a: Vec4,
+    b: Vec4,
+    c: Vec4,
+) {
+    rasterize_triangle(target.bounds(), a, b, c, |x, y, weights| {
+        let z = weights.interpolate(a.z, b.z, c.z);
+        let color = light.surface_light(weights).apply(color);
+        set_triangle_pixel(target, x, y, z, color, alpha_mode);
+    });
+}
 
-    // We also need to divide by the sum of the weights in order to renormalize these weights
-    // (get them back into a 0 to 1 range). The depth division probably shrunk the weights,
-    // and thus shrunk the texture space UV coordinate. This will result in a small corner of the texture being mapped
-    // over the entire object. To increase the UV coordinate back to their correct values we divide by the small weights
-    // and thus increase the UV coordinates.
+// Perspective correct barycentric weights of a screen space point in a screen space triangle.
+// These are used to interpolate vertex attributes (UVs, depth, normals, ...) across the triangle.
+// The weights have been divided by the depth of their vertex, so they are renormalized by their
+// sum when interpolating.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct PerspectiveWeights {
+    a: f32,
+    b: f32,
+    c: f32,
+    sum: f32,
+    // The change of a, b and c from this pixel to the next one to the right, and down.
+    step_x: Vec3,
+    step_y: Vec3,
+}
+
+impl PerspectiveWeights {
+    fn interpolate<T>(&self, a: T, b: T, c: T) -> T
+    where
+        T: Add<Output = T> + Mul<f32, Output = T> + Div<f32, Output = T>,
+    {
+        ((a * self.a) + (b * self.b) + (c * self.c)) / self.sum
+    }
 
-    let uv = ((a_uv * a_weight) + (b_uv * b_weight) + (c_uv * c_weight))
-        / (a_weight + b_weight + c_weight);
-    let w =
-        ((a4 * a_weight) + (b4 * b_weight) + (c4 * c_weight)) / (a_weight + b_weight + c_weight);
-    (uv, w.w)
+    // How much an interpolated value changes from this pixel to the next one to the right, and
+    // to the next one down.
+    fn derivatives<T>(&self, a: T, b: T, c: T) -> (T, T)
+    where
+        T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T> + Div<f32, Output = T>,
+    {
+        let value = self.interpolate(a, b, c);
+        let stepped = |step: Vec3| {
+            let weights = PerspectiveWeights {
+                a: self.a + step.x,
+                b: self.b + step.y,
+                c: self.c + step.z,
+                sum: self.sum + step.x + step.y + step.z,
+                ..*self
+            };
+            weights.interpolate(a, b, c) - value
+        };
+        (stepped(self.step_x), stepped(self.step_y))
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn draw_triangle_texture(
-    pixel_renderer: &mut PixelRenderer,
-    mesh: &Mesh,
-    light_intensity: f32,
+    target: &mut impl RenderTarget,
+    texture: &Texture,
+    filter: TextureFilter,
+    opacity: f32,
+    alpha_mode: AlphaMode,
+    light: TriangleLight,
     a: Vec4,
     b: Vec4,
     c: Vec4,
@@ -574,45 +1123,23 @@ pub fn draw_triangle_texture(
     b_uv: Vec2,
     c_uv: Vec2,
 ) {
-    let (x_min, x_max): (i32, i32) = min_max(a.x, b.x, c.x);
-    let (y_min, y_max): (i32, i32) = min_max(a.y, b.y, c.y);
-
-    let x_min = x_min.max(0);
-    let y_min = y_min.max(0);
-
-    let edge_from_a = b - a;
-    let edge_from_b = c - b;
-    let edge_from_c = a - c;
-
-    for x in x_min..=x_max {
-        for y in y_min..=y_max {
-            let p = Vec2::new(x as f32, y as f32);
-            let in_a = cross_edge(p, a.to_vec2(), edge_from_a.to_vec2());
-            let in_b = cross_edge(p, b.to_vec2(), edge_from_b.to_vec2());
-            let in_c = cross_edge(p, c.to_vec2(), edge_from_c.to_vec2());
-
-            if in_a == in_b && in_a == in_c {
-                let (uv, w) = interpolate_uv(p, a, b, c, a_uv, b_uv, c_uv);
-                let u = (((mesh.texture.width() - 1) as f32 * uv.x).round() as u32)
-                    .clamp(0, mesh.texture.width() - 1);
-                let v = (((mesh.texture.height() - 1) as f32 * (1.0 - uv.y)).round() as u32)
-                    .clamp(0, mesh.texture.height() - 1);
-                let texture_color = mesh.texture.get_pixel(u, v);
-                pixel_renderer.set_pixel_z(
-                    x as u32,
-                    y as u32,
-                    w,
-                    color_mul(
-                        Color::RGB(texture_color[0], texture_color[1], texture_color[2]),
-                        light_intensity,
-                    ),
-                );
-            }
-        }
-    }
+    rasterize_triangle(target.bounds(), a, b, c, |x, y, weights| {
+        let uv = weights.interpolate(a_uv, b_uv, c_uv);
+        let z = weights.interpolate(a.z, b.z, c.z);
+        let level_of_detail = if filter == TextureFilter::Trilinear {
+            let (uv_step_x, uv_step_y) = weights.derivatives(a_uv, b_uv, c_uv);
+            texture.level_of_detail(uv_step_x, uv_step_y)
+        } else {
+            0.0
+        };
+        let mut texture_color = texture.sample(uv, level_of_detail, filter);
+        texture_color.a = (texture_color.a as f32 * opacity).round() as u8;
+        let color = light.surface_light(weights).apply(texture_color);
+        set_triangle_pixel(target, x, y, z, color, alpha_mode);
+    });
 }
 
-pub fn draw_line(pixel_renderer: &mut PixelRenderer, color: Color, a: Vec4, b: Vec4) {
+pub fn draw_line(target: &mut impl RenderTarget, color: Color, a: Vec4, b: Vec4) {
     let mut x0: i32 = a.x.round() as i32;
     let mut y0: i32 = a.y.round() as i32;
     let x1: i32 = b.x.round() as i32;
@@ -627,7 +1154,7 @@ pub fn draw_line(pixel_renderer: &mut PixelRenderer, color: Color, a: Vec4, b: V
 
     if delta_x_abs > delta_y_abs {
         loop {
-            pixel_renderer.set_pixel(x0 as u32, y0 as u32, color);
+            target.set_pixel(x0 as u32, y0 as u32, color);
             if x0 == x1 {
                 break;
             }
@@ -650,7 +1177,7 @@ pub fn draw_line(pixel_renderer: &mut PixelRenderer, color: Color, a: Vec4, b: V
         }
     } else {
         loop {
-            pixel_renderer.set_pixel(x0 as u32, y0 as u32, color);
+            target.set_pixel(x0 as u32, y0 as u32, color);
             if y0 == y1 {
                 break;
             }
@@ -673,3 +1200,322 @@ pub fn draw_line(pixel_renderer: &mut PixelRenderer, color: Color, a: Vec4, b: V
         }
     }
 }
+
+#[test]
+fn test_draw_meshes_headless() {
+    let mut framebuffer = Framebuffer::new(200, 100, sdl2::pixels::PixelFormatEnum::ARGB8888);
+    let world = World {
+        meshes: vec![MeshPosition::new(
+            Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap(),
+            Vec3::new(0.0, 0.0, 0.0),
+        )],
+        camera: Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0)),
+        options: DrawOptions {
+            draw_wireframe: false,
+            triangle_fill: TriangleFill::Color,
+            shading: Shading::Flat,
+            backface_culling: true,
+            pause_rendering: true,
+            shuffle_faces: false,
+            tiled: false,
+            texture_filter: TextureFilter::Nearest,
+            shadows: Shadows::None,
+        },
+        lights: light::default_lights(),
+    };
+
+    draw_meshes(&mut framebuffer, &world);
+
+    assert_ne!(framebuffer.get_pixel(100, 50), Color::RGB(0, 0, 0));
+    assert_eq!(framebuffer.get_pixel(199, 50), Color::RGB(0, 0, 0));
+    assert!(framebuffer.z_buffer[(50 * 200) + 100] < f32::INFINITY);
+}
+
+#[test]
+fn test_mesh_position_model_matrix() {
+    let mesh = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap();
+    let mut mesh_position = MeshPosition {
+        rotation: Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 90.0),
+        scale: Vec3::new(2.0, 1.0, 1.0),
+        ..MeshPosition::new(mesh, Vec3::new(1.0, 2.0, 3.0))
+    };
+
+    // Scaled to (2, 0, 0), rotated to (0, 0, -2) and moved to (1, 2, 1).
+    let p = mesh_position.model_matrix() * Vec3::new(1.0, 0.0, 0.0);
+    assert!((p - Vec3::new(1.0, 2.0, 1.0)).len() < 1e-6);
+
+    // A normal of a surface stretched along x gets shorter along x.
+    let n = (mesh_position.normal_matrix() * Vec3::new(1.0, 1.0, 0.0)).unit_norm();
+    let expected = Vec3::new(0.0, 2.0, -1.0).unit_norm();
+    assert!((n - expected).len() < 1e-6);
+
+    // Mirrored along y, the normal of a surface facing up faces down.
+    mesh_position.scale = Vec3::new(2.0, -1.0, 1.0);
+    let n = (mesh_position.normal_matrix() * Vec3::new(0.0, 1.0, 0.0)).unit_norm();
+    assert!((n - Vec3::new(0.0, -1.0, 0.0)).len() < 1e-6);
+
+    // Flattened along y, everything faces along y.
+    mesh_position.scale = Vec3::new(2.0, 0.0, 1.0);
+    let n = (mesh_position.normal_matrix() * Vec3::new(1.0, 1.0, 0.0)).unit_norm();
+    assert!((n - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-6);
+}
+
+#[test]
+fn test_update_world_keeps_vertices() {
+    let mesh = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap();
+    let vertices = mesh.vertices.clone();
+    let mut world = World {
+        meshes: vec![MeshPosition {
+            angular_velocity: Vec3::new(10.0, 20.0, 30.0),
+            ..MeshPosition::new(mesh, Vec3::new(0.0, 0.0, 0.0))
+        }],
+        camera: Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0)),
+        options: DrawOptions {
+            draw_wireframe: false,
+            triangle_fill: TriangleFill::Color,
+            shading: Shading::Flat,
+            backface_culling: true,
+            pause_rendering: false,
+            shuffle_faces: false,
+            tiled: false,
+            texture_filter: TextureFilter::Nearest,
+            shadows: Shadows::None,
+        },
+        lights: light::default_lights(),
+    };
+
+    update_world(&mut world, 0.5);
+    assert_eq!(world.meshes[0].mesh.vertices, vertices);
+    assert_ne!(world.meshes[0].rotation, Quat::identity());
+}
+
+#[test]
+fn test_draw_meshes_aspect_ratio() {
+    // Looking straight at the cube, its front face should be square on screen at any framebuffer size.
+    for (width, height) in [(100, 100), (200, 100), (100, 200)] {
+        let mut framebuffer =
+            Framebuffer::new(width, height, sdl2::pixels::PixelFormatEnum::ARGB8888);
+        let world = World {
+            meshes: vec![MeshPosition::new(
+                Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap(),
+                Vec3::new(0.0, 0.0, 0.0),
+            )],
+            camera: Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0)),
+            options: DrawOptions {
+                draw_wireframe: false,
+                triangle_fill: TriangleFill::Color,
+                shading: Shading::Flat,
+                backface_culling: true,
+                pause_rendering: true,
+                shuffle_faces: false,
+                tiled: false,
+                texture_filter: TextureFilter::Nearest,
+                shadows: Shadows::None,
+            },
+            lights: light::default_lights(),
+        };
+        draw_meshes(&mut framebuffer, &world);
+
+        // Skip the top rows, where draw_mesh marks the corner of the framebuffer.
+        let covered: Vec<(u32, u32)> = (0..width)
+            .flat_map(|x| (11..height).map(move |y| (x, y)))
+            .filter(|&(x, y)| framebuffer.get_pixel(x, y) != Color::RGB(0, 0, 0))
+            .collect();
+        let covered_width =
+            covered.iter().map(|p| p.0).max().unwrap() - covered.iter().map(|p| p.0).min().unwrap();
+        let covered_height =
+            covered.iter().map(|p| p.1).max().unwrap() - covered.iter().map(|p| p.1).min().unwrap();
+        assert!(covered_width.abs_diff(covered_height) <= 1);
+    }
+}
+
+#[test]
+fn test_rasterize_triangle_matches_pixel_test() {
+    // Whole blocks accepted or rejected must agree with testing the edges of every pixel.
+    let triangles = [
+        [(2.0, 3.0), (250.0, 10.0), (20.0, 252.0)],
+        [(20.0, 252.0), (250.0, 10.0), (2.0, 3.0)],
+        [(2.0, 3.0), (250.0, 240.0), (245.0, 252.0)],
+        [(10.0, 10.0), (40.0, 10.0), (10.0, 40.0)],
+        [(-30.0, 5.5), (100.25, -7.0), (70.0, 90.75)],
+        [(5.0, 5.0), (7.0, 5.0), (5.0, 7.0)],
+    ];
+    let bounds = (0, 0, 127, 127);
+    for [(ax, ay), (bx, by), (cx, cy)] in triangles {
+        let a = Vec4::new(ax, ay, 1.0, 1.0);
+        let b = Vec4::new(bx, by, 1.0, 2.0);
+        let c = Vec4::new(cx, cy, 1.0, 3.0);
+
+        let mut rasterized = Vec::new();
+        rasterize_triangle(bounds, a, b, c, |x, y, _| rasterized.push((x, y)));
+        rasterized.sort();
+
+        let (sa, sb, sc) = (SubPixel::snap(a), SubPixel::snap(b), SubPixel::snap(c));
+        let edges = if EdgeFunction::new(sb, sc).at(sa) > 0 {
+            [(sb, sc), (sc, sa), (sa, sb)]
+        } else {
+            [(sc, sb), (sa, sc), (sb, sa)]
+        }
+        .map(|(from, to)| EdgeFunction::new(from, to));
+        let mut expected = Vec::new();
+        for y in 0..=127 {
+            for x in 0..=127 {
+                let p = SubPixel::pixel_centre(x, y);
+                if edges.iter().all(|edge| edge.at(p) >= edge.min_inside) {
+                    expected.push((x as u32, y as u32));
+                }
+            }
+        }
+        expected.sort();
+
+        assert!(!expected.is_empty());
+        assert_eq!(rasterized, expected);
+    }
+}
+
+#[cfg(test)]
+fn assert_covered_once(triangles: &[[Vec2; 3]], inside: impl Fn(Vec2) -> bool) {
+    let size = 128;
+    let mut hits = vec![0; size * size];
+    for [a, b, c] in triangles {
+        let [a, b, c] = [a, b, c].map(|v| Vec4::new(v.x, v.y, 1.0, 1.0));
+        let bounds = (0, 0, size as i32 - 1, size as i32 - 1);
+        rasterize_triangle(bounds, a, b, c, |x, y, _| {
+            hits[y as usize * size + x as usize] += 1;
+        });
+    }
+    for y in 0..size {
+        for x in 0..size {
+            let centre = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            let expected = if inside(centre) { 1 } else { 0 };
+            assert_eq!(hits[y * size + x], expected, "pixel {} {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn test_rasterize_grid_covers_pixels_once() {
+    // A rectangle split into quads, with the inner vertices moved off the pixel grid, and each
+    // quad split into two triangles with alternating diagonals and winding.
+    let (x0, y0, x1, y1) = (3.3, 2.7, 120.6, 97.2);
+    let n = 7;
+    let vertex = |i: usize, j: usize| {
+        let mut x = x0 + (x1 - x0) * i as f32 / n as f32;
+        let mut y = y0 + (y1 - y0) * j as f32 / n as f32;
+        if i > 0 && i < n && j > 0 && j < n {
+            x += 4.0 * ((i * 7 + j * 3) as f32).sin();
+            y += 4.0 * ((i * 5 + j * 11) as f32).cos();
+        }
+        Vec2::new(x, y)
+    };
+    let mut triangles = Vec::new();
+    for j in 0..n {
+        for i in 0..n {
+            let (p00, p10) = (vertex(i, j), vertex(i + 1, j));
+            let (p01, p11) = (vertex(i, j + 1), vertex(i + 1, j + 1));
+            if (i + j) % 2 == 0 {
+                triangles.push([p00, p10, p11]);
+                triangles.push([p00, p01, p11]);
+            } else {
+                triangles.push([p10, p00, p01]);
+                triangles.push([p10, p11, p01]);
+            }
+        }
+    }
+    assert_covered_once(&triangles, |p| p.x > x0 && p.x < x1 && p.y > y0 && p.y < y1);
+}
+
+#[test]
+fn test_rasterize_fan_covers_pixels_once() {
+    // Triangles meeting at a vertex on a pixel centre, with edges through many pixel centres.
+    let centre = Vec2::new(64.5, 64.5);
+    let corners = [
+        Vec2::new(0.5, 0.5),
+        Vec2::new(64.5, 0.5),
+        Vec2::new(127.5, 0.5),
+        Vec2::new(127.5, 64.5),
+        Vec2::new(127.5, 127.5),
+        Vec2::new(64.5, 127.5),
+        Vec2::new(0.5, 127.5),
+        Vec2::new(0.5, 64.5),
+    ];
+    let triangles: Vec<[Vec2; 3]> = (0..corners.len())
+        .map(|i| [centre, corners[i], corners[(i + 1) % corners.len()]])
+        .collect();
+    // The square through the outermost pixel centres: its top and left edges are drawn, its
+    // bottom and right edges are not.
+    assert_covered_once(&triangles, |p| {
+        p.x >= 0.5 && p.x < 127.5 && p.y >= 0.5 && p.y < 127.5
+    });
+}
+
+#[test]
+fn test_perspective_weights_derivatives() {
+    // With the same depth at every vertex, UVs change linearly across the screen.
+    let a = Vec4::new(0.0, 0.0, 1.0, 2.0);
+    let b = Vec4::new(64.0, 0.0, 1.0, 2.0);
+    let c = Vec4::new(0.0, 32.0, 1.0, 2.0);
+    let (a_uv, b_uv, c_uv) = (
+        Vec2::new(0.0, 0.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(0.0, 1.0),
+    );
+    let mut pixels = 0;
+    rasterize_triangle((0, 0, 63, 31), a, b, c, |_, _, weights| {
+        let (step_x, step_y) = weights.derivatives(a_uv, b_uv, c_uv);
+        assert!(step_x.approx_eq(Vec2::new(1.0 / 64.0, 0.0), 1e-5));
+        assert!(step_y.approx_eq(Vec2::new(0.0, 1.0 / 32.0), 1e-5));
+        pixels += 1;
+    });
+    assert!(pixels > 0);
+}
+
+#[test]
+fn test_sort_blended_last() {
+    let light = TriangleLight::Flat(SurfaceLight {
+        diffuse: Vec3::new(1.0, 1.0, 1.0),
+        specular: Vec3::new(0.0, 0.0, 0.0),
+    });
+    let triangle = |z: f32, alpha_mode: AlphaMode| DrawCommand::TriangleColor {
+        color: Color::RGBA(255, 0, 0, 128),
+        alpha_mode,
+        light,
+        vertices: [
+            Vec4::new(0.0, 0.0, z, z),
+            Vec4::new(10.0, 0.0, z, z),
+            Vec4::new(0.0, 10.0, z, z),
+        ],
+    };
+    let mut commands = vec![
+        triangle(2.0, AlphaMode::Blend),
+        triangle(3.0, AlphaMode::Opaque),
+        triangle(9.0, AlphaMode::Blend),
+        DrawCommand::Line {
+            color: Color::RGB(255, 255, 255),
+            a: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            b: Vec4::new(5.0, 5.0, 1.0, 1.0),
+        },
+        triangle(1.0, AlphaMode::Mask(0.5)),
+        triangle(4.0, AlphaMode::Blend),
+    ];
+    sort_blended_last(&mut commands);
+
+    // Opaque and masked triangles keep their order, then blended ones go from far to near.
+    assert!(matches!(
+        commands[0],
+        DrawCommand::TriangleColor {
+            alpha_mode: AlphaMode::Opaque,
+            ..
+        }
+    ));
+    assert!(matches!(commands[1], DrawCommand::Line { .. }));
+    assert!(matches!(
+        commands[2],
+        DrawCommand::TriangleColor {
+            alpha_mode: AlphaMode::Mask(_),
+            ..
+        }
+    ));
+    let blended: Vec<Option<f32>> = commands[3..].iter().map(|c| c.blended_depth()).collect();
+    assert_eq!(blended, vec![Some(9.0), Some(4.0), Some(2.0)]);
+}
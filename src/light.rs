@@ -0,0 +1,397 @@
+// Light sources and Blinn-Phong shading. Lights are in world space, and light is an RGB multiplier
+// for a color: 1.0 leaves a channel as it is, and more than 1.0 brightens it.
+use crate::material::Material;
+use crate::shadow::ShadowMap;
+use crate::vec::Vec3;
+use sdl2::pixels::Color;
+
+// How point and spot lights fade with distance d: the light is divided by
+// constant + linear * d + quadratic * d * d.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    // No fading.
+    pub const NONE: Attenuation = Attenuation {
+        constant: 1.0,
+        linear: 0.0,
+        quadratic: 0.0,
+    };
+
+    pub fn factor(&self, distance: f32) -> f32 {
+        let divisor = self.constant + self.linear * distance + self.quadratic * distance * distance;
+        if divisor <= 0.0 {
+            return 1.0;
+        }
+        1.0 / divisor
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Light {
+    // The same light everywhere, from every direction.
+    Ambient {
+        color: Color,
+        intensity: f32,
+    },
+    // A light infinitely far away, like the sun. The direction is the way the light travels.
+    Directional {
+        direction: Vec3,
+        color: Color,
+        intensity: f32,
+    },
+    // A light shining in all directions from a position.
+    Point {
+        position: Vec3,
+        color: Color,
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+    // A light shining from a position in a cone around a direction. Inside the inner angle the
+    // light is at full intensity, and it fades out towards the outer angle. Angles are in degrees
+    // from the direction.
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        inner_angle: f32,
+        outer_angle: f32,
+        color: Color,
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+}
+
+fn color_to_vec3(color: Color) -> Vec3 {
+    Vec3::new(color.r as f32, color.g as f32, color.b as f32) / 255.0
+}
+
+impl Light {
+    // The color of the light times its intensity.
+    pub fn radiance(&self) -> Vec3 {
+        match *self {
+            Light::Ambient { color, intensity }
+            | Light::Directional {
+                color, intensity, ..
+            }
+            | Light::Point {
+                color, intensity, ..
+            }
+            | Light::Spot {
+                color, intensity, ..
+            } => color_to_vec3(color) * intensity,
+        }
+    }
+
+    // The unit direction from a point towards the light, and how much of the light reaches the
+    // point, from the distance and the cone of a spot light. None for ambient light, which has no
+    // direction.
+    pub fn incoming(&self, position: Vec3) -> Option<(Vec3, f32)> {
+        match *self {
+            Light::Ambient { .. } => None,
+            Light::Directional { direction, .. } => Some((-direction.unit_norm(), 1.0)),
+            Light::Point {
+                position: light_position,
+                attenuation,
+                ..
+            } => {
+                let to_light = light_position - position;
+                let distance = to_light.len();
+                Some((to_light.unit_norm(), attenuation.factor(distance)))
+            }
+            Light::Spot {
+                position: light_position,
+                direction,
+                inner_angle,
+                outer_angle,
+                attenuation,
+                ..
+            } => {
+                let to_light = light_position - position;
+                let distance = to_light.len();
+                let to_light = to_light.unit_norm();
+                let cos_angle = (-to_light).dot(direction.unit_norm());
+                let cos_inner = inner_angle.to_radians().cos();
+                let cos_outer = outer_angle.to_radians().cos();
+                let cone = if cos_inner > cos_outer {
+                    ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0)
+                } else if cos_angle >= cos_outer {
+                    1.0
+                } else {
+                    0.0
+                };
+                Some((to_light, attenuation.factor(distance) * cone))
+            }
+        }
+    }
+}
+
+// The light reflected by a surface towards the eye. The diffuse part, which includes the reflected
+// ambient light, multiplies the color of the surface (its texture or diffuse color). The specular
+// part is the highlight, added on top in the color of the light.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SurfaceLight {
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+}
+
+impl SurfaceLight {
+    // The color of a surface with this light on it. Alpha is kept.
+    pub fn apply(&self, color: Color) -> Color {
+        let rgb = color_to_vec3(color) * self.diffuse + self.specular;
+        let [r, g, b] = <[f32; 3]>::from(rgb * 255.0).map(|c| c.clamp(0.0, 255.0).round() as u8);
+        Color::RGBA(r, g, b, color.a)
+    }
+}
+
+// Blinn-Phong shading of a surface at a position with a unit normal, seen from the eye.
+//
+// Ambient lights are reflected by the ambient color of the material (Ka), relative to the color of
+// the surface. Other lights are reflected diffusely by how directly they face the surface, and
+// specularly by how closely the normal matches the half vector between the directions to the
+// light and to the eye. The specular color (Ks) tints the highlight and the shininess (Ns)
+// narrows it.
+//
+// shadow_maps has the shadow map of each light, if it has one. It can be shorter than lights.
+pub fn shade(
+    lights: &[Light],
+    shadow_maps: &[Option<ShadowMap>],
+    material: &Material,
+    position: Vec3,
+    normal: Vec3,
+    eye: Vec3,
+) -> SurfaceLight {
+    let ambient_color = color_to_vec3(material.ambient);
+    let specular_color = color_to_vec3(material.specular);
+    let has_specular = specular_color != Vec3::new(0.0, 0.0, 0.0);
+    let to_eye = (eye - position).unit_norm();
+
+    let mut light = SurfaceLight {
+        diffuse: Vec3::new(0.0, 0.0, 0.0),
+        specular: Vec3::new(0.0, 0.0, 0.0),
+    };
+    for (i, source) in lights.iter().enumerate() {
+        let Some((to_light, mut amount)) = source.incoming(position) else {
+            light.diffuse += source.radiance() * ambient_color;
+            continue;
+        };
+        let facing = normal.dot(to_light);
+        if facing <= 0.0 || amount <= 0.0 {
+            continue;
+        }
+        if let Some(Some(shadow_map)) = shadow_maps.get(i) {
+            amount *= shadow_map.visibility(position, facing);
+            if amount <= 0.0 {
+                continue;
+            }
+        }
+        let radiance = source.radiance() * amount;
+        light.diffuse += radiance * facing;
+        if has_specular {
+            let half = (to_light + to_eye).unit_norm();
+            let highlight = normal.dot(half).max(0.0).powf(material.shininess);
+            light.specular += radiance * specular_color * highlight;
+        }
+    }
+    light
+}
+
+// A white ambient light and a white light from the upper left, behind the camera.
+pub fn default_lights() -> Vec<Light> {
+    vec![
+        Light::Ambient {
+            color: Color::RGB(255, 255, 255),
+            intensity: 1.0,
+        },
+        Light::Directional {
+            direction: Vec3::new(100.0, -100.0, 50.0),
+            color: Color::RGB(255, 255, 255),
+            intensity: 0.8,
+        },
+    ]
+}
+
+#[cfg(test)]
+fn assert_light_near(actual: Vec3, expected: Vec3) {
+    assert!(
+        actual.approx_eq(expected, 1e-5),
+        "{:?} != {:?}",
+        actual,
+        expected
+    );
+}
+
+#[cfg(test)]
+fn diffuse_light(light: Light, position: Vec3, normal: Vec3) -> Vec3 {
+    // A material that reflects all of the ambient light and has no highlights.
+    let material = Material {
+        ambient: Color::RGB(255, 255, 255),
+        ..Material::default()
+    };
+    let eye = position + normal;
+    let light = shade(&[light], &[], &material, position, normal, eye);
+    assert_eq!(light.specular, Vec3::new(0.0, 0.0, 0.0));
+    light.diffuse
+}
+
+#[test]
+fn test_directional_and_ambient_light() {
+    let white = Color::RGB(255, 255, 255);
+    let sun = Light::Directional {
+        direction: Vec3::new(0.0, -2.0, 0.0),
+        color: Color::RGB(255, 0, 255),
+        intensity: 2.0,
+    };
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let origin = Vec3::new(0.0, 0.0, 0.0);
+    assert_eq!(sun.incoming(origin), Some((up, 1.0)));
+    assert_light_near(diffuse_light(sun, origin, up), Vec3::new(2.0, 0.0, 2.0));
+    // At 60 degrees, half the light falls on the surface.
+    let tilted = Vec3::new(60_f32.to_radians().sin(), 60_f32.to_radians().cos(), 0.0);
+    assert_light_near(diffuse_light(sun, origin, tilted), Vec3::new(1.0, 0.0, 1.0));
+    // Facing away.
+    assert_light_near(diffuse_light(sun, origin, -up), Vec3::new(0.0, 0.0, 0.0));
+
+    let ambient = Light::Ambient {
+        color: white,
+        intensity: 0.25,
+    };
+    assert_eq!(ambient.incoming(origin), None);
+    assert_light_near(
+        diffuse_light(ambient, origin, -up),
+        Vec3::new(0.25, 0.25, 0.25),
+    );
+}
+
+#[test]
+fn test_point_light_attenuation() {
+    let light = Light::Point {
+        position: Vec3::new(0.0, 4.0, 0.0),
+        color: Color::RGB(255, 255, 255),
+        intensity: 1.0,
+        attenuation: Attenuation {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.25,
+        },
+    };
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    // 4 units away: 1 / (1 + 2 + 4).
+    let (to_light, amount) = light.incoming(Vec3::new(0.0, 0.0, 0.0)).unwrap();
+    assert_light_near(to_light, up);
+    assert!((amount - 1.0 / 7.0).abs() < 1e-6);
+    let below = diffuse_light(light, Vec3::new(0.0, 0.0, 0.0), up);
+    assert_light_near(below, Vec3::new(1.0, 1.0, 1.0) / 7.0);
+    // 2 units away: 1 / (1 + 1 + 1).
+    let closer = diffuse_light(light, Vec3::new(0.0, 2.0, 0.0), up);
+    assert_light_near(closer, Vec3::new(1.0, 1.0, 1.0) / 3.0);
+    // Above the light, the surface faces away from it.
+    let (to_light, _) = light.incoming(Vec3::new(0.0, 5.0, 0.0)).unwrap();
+    assert_light_near(to_light, -up);
+    let above = diffuse_light(light, Vec3::new(0.0, 5.0, 0.0), up);
+    assert_light_near(above, Vec3::new(0.0, 0.0, 0.0));
+}
+
+#[test]
+fn test_spot_light_cone() {
+    // Shining straight down from 1 unit above the floor.
+    let light = Light::Spot {
+        position: Vec3::new(0.0, 1.0, 0.0),
+        direction: Vec3::new(0.0, -1.0, 0.0),
+        inner_angle: 30.0,
+        outer_angle: 60.0,
+        color: Color::RGB(255, 255, 255),
+        intensity: 1.0,
+        attenuation: Attenuation::NONE,
+    };
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let at_angle = |degrees: f32| {
+        let floor = Vec3::new(degrees.to_radians().tan(), 0.0, 0.0);
+        light.incoming(floor).unwrap().1
+    };
+    assert_eq!(at_angle(0.0), 1.0);
+    assert_eq!(at_angle(20.0), 1.0);
+    assert!(at_angle(45.0) > 0.0 && at_angle(45.0) < 1.0);
+    assert!(at_angle(40.0) > at_angle(50.0));
+    assert_eq!(at_angle(70.0), 0.0);
+    assert_light_near(
+        diffuse_light(light, Vec3::new(0.0, 0.0, 0.0), up),
+        Vec3::new(1.0, 1.0, 1.0),
+    );
+    // Outside the cone the floor gets no light.
+    assert_light_near(
+        diffuse_light(light, Vec3::new(2.0, 0.0, 0.0), up),
+        Vec3::new(0.0, 0.0, 0.0),
+    );
+}
+
+#[test]
+fn test_shade_blinn_phong() {
+    let material = Material {
+        ambient: Color::RGB(51, 51, 51),
+        specular: Color::RGB(255, 255, 0),
+        shininess: 16.0,
+        ..Material::default()
+    };
+    let lights = [
+        Light::Ambient {
+            color: Color::RGB(255, 255, 255),
+            intensity: 1.0,
+        },
+        // From straight above.
+        Light::Directional {
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            color: Color::RGB(255, 255, 255),
+            intensity: 1.0,
+        },
+    ];
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let origin = Vec3::new(0.0, 0.0, 0.0);
+
+    // The eye straight above sees the full highlight, in the specular color.
+    let above = shade(
+        &lights,
+        &[],
+        &material,
+        origin,
+        up,
+        Vec3::new(0.0, 5.0, 0.0),
+    );
+    assert_light_near(above.diffuse, Vec3::new(1.2, 1.2, 1.2));
+    assert_light_near(above.specular, Vec3::new(1.0, 1.0, 0.0));
+
+    // At 90 degrees the half vector is 45 degrees from the normal.
+    let side = shade(
+        &lights,
+        &[],
+        &material,
+        origin,
+        up,
+        Vec3::new(5.0, 0.0, 0.0),
+    );
+    assert_light_near(side.diffuse, above.diffuse);
+    let highlight = 45_f32.to_radians().cos().powf(16.0);
+    assert_light_near(side.specular, Vec3::new(highlight, highlight, 0.0));
+
+    // A surface facing away only gets the ambient light.
+    let below = shade(
+        &lights,
+        &[],
+        &material,
+        origin,
+        -up,
+        Vec3::new(0.0, -5.0, 0.0),
+    );
+    assert_light_near(below.diffuse, Vec3::new(0.2, 0.2, 0.2));
+    assert_light_near(below.specular, Vec3::new(0.0, 0.0, 0.0));
+
+    // The diffuse light multiplies the color and the highlight is added.
+    assert_eq!(
+        above.apply(Color::RGB(100, 0, 200)),
+        Color::RGB(255, 255, 240)
+    );
+    assert_eq!(below.apply(Color::RGB(100, 0, 200)), Color::RGB(20, 0, 40));
+}
@@ -1,39 +1,59 @@
+use graphics_from_scratch::camera::{Camera, Projection};
+use graphics_from_scratch::light::default_lights;
 use graphics_from_scratch::mesh::Mesh;
 use graphics_from_scratch::pixel_renderer::PixelRenderer;
+use graphics_from_scratch::shadow::Shadows;
+use graphics_from_scratch::texture::TextureFilter;
 use graphics_from_scratch::vec::Vec3;
 use graphics_from_scratch::{
     draw_meshes, update_world, update_world_motion, update_world_rotate, DrawOptions, MeshPosition,
-    TriangleFill, World,
+    Shading, TriangleFill, World,
 };
 use sdl2::keyboard::{KeyboardState, Scancode};
+use sdl2::pixels::Color;
+
+fn load_mesh_or_exit(obj_file_path: &str, texture_file_path: &str) -> Mesh {
+    match Mesh::load_mesh(obj_file_path, texture_file_path) {
+        Ok(mesh) => mesh,
+        Err(e) => {
+            eprintln!("Failed to load mesh: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
 fn main() {
     let width = 860;
     let height = 360;
     assert!(width == 3440 / 4);
     assert!(height == 1440 / 4);
-    let f22_mesh = Mesh::load_mesh("./assets/f22.obj", "./assets/f22.png");
-    let cube_mesh = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png");
+    let mut f22_mesh = load_mesh_or_exit("./assets/f22.obj", "./assets/f22.png");
+    // A metallic sheen.
+    for material in &mut f22_mesh.materials {
+        material.specular = Color::RGB(200, 200, 200);
+        material.shininess = 32.0;
+    }
+    let cube_mesh = load_mesh_or_exit("./assets/cube.obj", "./assets/cube.png");
     let mut pixel_renderer = PixelRenderer::new(width, height);
+    let mut framebuffer = pixel_renderer.new_framebuffer();
     let mut world = World {
         meshes: vec![
-            MeshPosition {
-                mesh: f22_mesh,
-                position: Vec3::new(-2.0, 0.0, 0.0),
-            },
-            MeshPosition {
-                mesh: cube_mesh,
-                position: Vec3::new(2.0, 0.0, 0.0),
-            },
+            MeshPosition::new(f22_mesh, Vec3::new(-2.0, 0.0, 0.0)),
+            MeshPosition::new(cube_mesh, Vec3::new(2.0, 0.0, 0.0)),
         ],
-        camera_location: Vec3::new(0.0, 0.0, -5.0),
-        camera_look_at: Vec3::new(0.0, 0.0, 0.0),
+        camera: Camera::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 0.0)),
         options: DrawOptions {
             draw_wireframe: false,
             triangle_fill: TriangleFill::Texture,
+            shading: Shading::Flat,
             backface_culling: true,
             pause_rendering: true,
+            shuffle_faces: true,
+            tiled: true,
+            texture_filter: TextureFilter::Trilinear,
+            shadows: Shadows::None,
         },
+        lights: default_lights(),
     };
     let mut prior_instant: std::time::Instant = std::time::Instant::now();
     'main_loop: loop {
@@ -71,6 +91,73 @@ fn main() {
                 } => {
                     draw_options.pause_rendering = !draw_options.pause_rendering;
                 }
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::Num5),
+                    ..
+                } => match draw_options.shading {
+                    Shading::Flat => draw_options.shading = Shading::Gouraud,
+                    Shading::Gouraud => draw_options.shading = Shading::Phong,
+                    Shading::Phong => draw_options.shading = Shading::Flat,
+                },
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::Num6),
+                    ..
+                } => {
+                    let camera = &mut world.camera;
+                    camera.projection = match camera.projection {
+                        Projection::Perspective => {
+                            // Keep the meshes around the origin the same size.
+                            camera.extent = camera.extent_at_distance(camera.location.len());
+                            Projection::Orthographic
+                        }
+                        Projection::Orthographic => Projection::Perspective,
+                    };
+                }
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::Num7),
+                    ..
+                } => {
+                    draw_options.tiled = !draw_options.tiled;
+                }
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::Num8),
+                    ..
+                } => {
+                    draw_options.texture_filter = match draw_options.texture_filter {
+                        TextureFilter::Nearest => TextureFilter::Bilinear,
+                        TextureFilter::Bilinear => TextureFilter::Trilinear,
+                        TextureFilter::Trilinear => TextureFilter::Nearest,
+                    };
+                }
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::Num9),
+                    ..
+                } => {
+                    draw_options.shadows = match draw_options.shadows {
+                        Shadows::None => Shadows::Hard,
+                        Shadows::Hard => Shadows::Pcf,
+                        Shadows::Pcf => Shadows::None,
+                    };
+                }
+                sdl2::event::Event::MouseWheel { y, .. } => {
+                    // Moving closer does not make things bigger with orthographic projection,
+                    // so the mouse wheel zooms by changing the extent.
+                    world.camera.extent *= 0.9_f32.powi(y);
+                }
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::F12),
+                    ..
+                } => {
+                    let seconds_since_epoch = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    let file_path = format!("./screenshot_{}.png", seconds_since_epoch);
+                    match framebuffer.save(&file_path) {
+                        Ok(()) => println!("Saved screenshot to {}", file_path),
+                        Err(e) => eprintln!("Failed to save screenshot to {}: {}", file_path, e),
+                    }
+                }
                 sdl2::event::Event::MouseMotion { xrel, yrel, .. } => {
                     update_world_rotate(&mut world, (xrel, yrel));
                 }
@@ -104,7 +191,7 @@ fn main() {
         update_world_motion(&mut world, motion_vec, delta_t);
         update_world(&mut world, delta_t);
         prior_instant = std::time::Instant::now();
-        draw_meshes(&mut pixel_renderer, &world);
-        pixel_renderer.render();
+        draw_meshes(&mut framebuffer, &world);
+        pixel_renderer.render(&framebuffer);
     }
 }
@@ -99,6 +99,157 @@ impl Mat4 {
 	)
     }
 
+    /// Rotation around an arbitrary axis, counterclockwise when looking down the axis
+    /// towards the origin (like rotate_x, rotate_y and rotate_z). The axis does not need to be normal.
+    #[rustfmt::skip]
+    pub fn rotate_axis(axis: Vec3, angle_degrees: f32) -> Self {
+	let Vec3 { x, y, z } = axis.unit_norm();
+	let (s, c) = angle_degrees.to_radians().sin_cos();
+	let t = 1.0 - c;
+	Self::new(
+	    t * x * x + c,     t * x * y - s * z, t * x * z + s * y, 0.0,
+	    t * x * y + s * z, t * y * y + c,     t * y * z - s * x, 0.0,
+	    t * x * z - s * y, t * y * z + s * x, t * z * z + c,     0.0,
+	    0.0,               0.0,               0.0,               1.0,
+	)
+    }
+
+    /// The view matrix of a camera at eye looking towards target: it moves the world so the camera
+    /// is at the origin looking along +z, with up pointing along +y.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        // Conceptually, we will create a set of orthonormal basis vectors and the Z basis vector will point
+        // towards target. Once we have the matrix to transform the standard basis vectors we will find the
+        // inverse (by simply transposing, since it's orthonormal) and apply the inverse to the world.
+        // Applying the inverse to the world will transform the world and place target along the standard z axis.
+        // (We will not store the intermediate matrix, we will calculate and return the inverse matrix directly.)
+
+        // The axes that are aligned with target, we will call "aligned".
+        let aligned_z_axis = (target - eye).unit_norm();
+        let aligned_x_axis = up.cross(aligned_z_axis).unit_norm();
+        // When cross arguments are already normal, the result will be normal.
+        let aligned_y_axis = aligned_z_axis.cross(aligned_x_axis);
+
+        #[rustfmt::skip]
+        let view = Self::new(
+            aligned_x_axis.x, aligned_x_axis.y, aligned_x_axis.z, -aligned_x_axis.dot(eye),
+            aligned_y_axis.x, aligned_y_axis.y, aligned_y_axis.z, -aligned_y_axis.dot(eye),
+            aligned_z_axis.x, aligned_z_axis.y, aligned_z_axis.z, -aligned_z_axis.dot(eye),
+            0.0,              0.0,              0.0,              1.0,
+        );
+        view
+    }
+
+    /// Perspective projection of camera space (looking along +z) to clip space.
+    /// fov_degrees is the vertical field of view and aspect_ratio is width / height.
+    /// After dividing by w, x and y are -1.0 to 1.0 with y pointing down (like the screen), z is 0.0
+    /// at the near plane and 1.0 at the far plane, and w is the camera space z.
+    #[rustfmt::skip]
+    pub fn perspective(fov_degrees: f32, aspect_ratio: f32, z_near: f32, z_far: f32) -> Self {
+	//  f: f32 = 1.732_051;  (1 / (tan(fov / 2)) for a fov of 60 degrees)
+	let f: f32 = 1.0 / ((fov_degrees.to_radians() / 2.0).tan());
+	let z_ratio = z_far / (z_far - z_near);
+	Self::new(
+	    f / aspect_ratio, 0.0, 0.0,     0.0,
+	    0.0,              -f,  0.0,     0.0,
+	    0.0,              0.0, z_ratio, -z_ratio * z_near,
+	    0.0,              0.0, 1.0,     0.0,
+	)
+    }
+
+    /// Orthographic projection of the box between left and right, bottom and top and z_near and z_far
+    /// in camera space to clip space, with the same ranges as perspective. w is always 1.0.
+    #[rustfmt::skip]
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, z_near: f32, z_far: f32) -> Self {
+	let width = right - left;
+	let height = top - bottom;
+	let depth = z_far - z_near;
+	Self::new(
+	    2.0 / width, 0.0,           0.0,         -(right + left) / width,
+	    0.0,         -2.0 / height, 0.0,         (top + bottom) / height,
+	    0.0,         0.0,           1.0 / depth, -z_near / depth,
+	    0.0,         0.0,           0.0,         1.0,
+	)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.sub_determinants();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    // The determinants of the 2x2 sub-matrices in the top two rows (s) and the bottom two rows (c).
+    // Laplace expansion along the top two rows combines them into the determinant, and they are
+    // shared by the cofactors that make up the inverse.
+    fn sub_determinants(&self) -> ([f32; 6], [f32; 6]) {
+        let a = |row: usize, column: usize| self.get(row, column);
+        let pair = |r0: usize, r1: usize, c0: usize, c1: usize| {
+            a(r0, c0) * a(r1, c1) - a(r1, c0) * a(r0, c1)
+        };
+        (
+            [
+                pair(0, 1, 0, 1),
+                pair(0, 1, 0, 2),
+                pair(0, 1, 0, 3),
+                pair(0, 1, 1, 2),
+                pair(0, 1, 1, 3),
+                pair(0, 1, 2, 3),
+            ],
+            [
+                pair(2, 3, 0, 1),
+                pair(2, 3, 0, 2),
+                pair(2, 3, 0, 3),
+                pair(2, 3, 1, 2),
+                pair(2, 3, 1, 3),
+                pair(2, 3, 2, 3),
+            ],
+        )
+    }
+
+    /// The inverse matrix, or None when the matrix is singular (its determinant is zero).
+    #[rustfmt::skip]
+    pub fn inverse(&self) -> Option<Mat4> {
+        let (s, c) = self.sub_determinants();
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let a = |row: usize, column: usize| self.get(row, column);
+        let inverse = Mat4::new(
+             a(1, 1) * c[5] - a(1, 2) * c[4] + a(1, 3) * c[3],
+            -a(0, 1) * c[5] + a(0, 2) * c[4] - a(0, 3) * c[3],
+             a(3, 1) * s[5] - a(3, 2) * s[4] + a(3, 3) * s[3],
+            -a(2, 1) * s[5] + a(2, 2) * s[4] - a(2, 3) * s[3],
+
+            -a(1, 0) * c[5] + a(1, 2) * c[2] - a(1, 3) * c[1],
+             a(0, 0) * c[5] - a(0, 2) * c[2] + a(0, 3) * c[1],
+            -a(3, 0) * s[5] + a(3, 2) * s[2] - a(3, 3) * s[1],
+             a(2, 0) * s[5] - a(2, 2) * s[2] + a(2, 3) * s[1],
+
+             a(1, 0) * c[4] - a(1, 1) * c[2] + a(1, 3) * c[0],
+            -a(0, 0) * c[4] + a(0, 1) * c[2] - a(0, 3) * c[0],
+             a(3, 0) * s[4] - a(3, 1) * s[2] + a(3, 3) * s[0],
+            -a(2, 0) * s[4] + a(2, 1) * s[2] - a(2, 3) * s[0],
+
+            -a(1, 0) * c[3] + a(1, 1) * c[1] - a(1, 2) * c[0],
+             a(0, 0) * c[3] - a(0, 1) * c[1] + a(0, 2) * c[0],
+            -a(3, 0) * s[3] + a(3, 1) * s[1] - a(3, 2) * s[0],
+             a(2, 0) * s[3] - a(2, 1) * s[1] + a(2, 2) * s[0],
+        );
+        Some(inverse * (1.0 / det))
+    }
+
+    /// Transform a point: the translation applies, and the result is divided by w, so this also works
+    /// for projection matrices.
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let p = *self * p.to_vec4();
+        Vec3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+    }
+
+    /// Transform a direction: the translation does not apply.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let v = *self * Vec4::new(v.x, v.y, v.z, 0.0);
+        Vec3::new(v.x, v.y, v.z)
+    }
+
     #[rustfmt::skip]
     pub fn transpose(&self) -> Mat4 {
         Mat4::new(
@@ -128,6 +279,123 @@ impl Mat4 {
     }
 }
 
+// Matrix products, the hottest math in the renderer: every vertex is multiplied by several matrices
+// every frame. On x86_64 the * operators use SSE2 (always available there), elsewhere the scalar
+// versions. Both add the products in the same order without fused multiply-adds, so they give
+// exactly the same results. Both are public so the benchmarks can compare them.
+impl Mat4 {
+    #[rustfmt::skip]
+    pub fn mul_mat4_scalar(&self, other: &Self) -> Self {
+        let mut data = std::mem::MaybeUninit::<[f32; 16]>::uninit();
+        let data_p = data.as_mut_ptr() as *mut f32;
+
+        for column in 0..4 {
+            for row in 0..4 {
+                let i = (column * 4) + row;
+                unsafe {
+                    *data_p.add(i) =
+                          self.get(row, 0) * other.get(0, column)
+                        + self.get(row, 1) * other.get(1, column)
+                        + self.get(row, 2) * other.get(2, column)
+                        + self.get(row, 3) * other.get(3, column);
+                }
+            }
+        }
+
+        unsafe {
+            Self {
+                data: data.assume_init(),
+            }
+        }
+    }
+
+    pub fn mul_vec4_scalar(&self, other: Vec4) -> Vec4 {
+        Vec4::new(
+            (self.get(0, 0) * other.x)
+                + (self.get(0, 1) * other.y)
+                + (self.get(0, 2) * other.z)
+                + (self.get(0, 3) * other.w),
+            (self.get(1, 0) * other.x)
+                + (self.get(1, 1) * other.y)
+                + (self.get(1, 2) * other.z)
+                + (self.get(1, 3) * other.w),
+            (self.get(2, 0) * other.x)
+                + (self.get(2, 1) * other.y)
+                + (self.get(2, 2) * other.z)
+                + (self.get(2, 3) * other.w),
+            (self.get(3, 0) * other.x)
+                + (self.get(3, 1) * other.y)
+                + (self.get(3, 2) * other.z)
+                + (self.get(3, 3) * other.w),
+        )
+    }
+
+    // The data is column major, so each column loads straight into a register. A matrix times a
+    // vector is then the sum of the columns scaled by the components of the vector.
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    #[inline]
+    fn columns_sse2(&self) -> [std::arch::x86_64::__m128; 4] {
+        use std::arch::x86_64::_mm_loadu_ps;
+        let p = self.data.as_ptr();
+        // Safety: data holds 16 f32s, so each load of 4 is in bounds. loadu has no alignment
+        // requirement.
+        unsafe {
+            [
+                _mm_loadu_ps(p),
+                _mm_loadu_ps(p.add(4)),
+                _mm_loadu_ps(p.add(8)),
+                _mm_loadu_ps(p.add(12)),
+            ]
+        }
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    #[inline]
+    fn combine_columns_sse2(
+        columns: &[std::arch::x86_64::__m128; 4],
+        x: f32,
+        y: f32,
+        z: f32,
+        w: f32,
+    ) -> std::arch::x86_64::__m128 {
+        use std::arch::x86_64::{_mm_add_ps, _mm_mul_ps, _mm_set1_ps};
+        // Safety: only compiled when SSE2 (which includes SSE) is enabled for the target.
+        unsafe {
+            let sum = _mm_mul_ps(columns[0], _mm_set1_ps(x));
+            let sum = _mm_add_ps(sum, _mm_mul_ps(columns[1], _mm_set1_ps(y)));
+            let sum = _mm_add_ps(sum, _mm_mul_ps(columns[2], _mm_set1_ps(z)));
+            _mm_add_ps(sum, _mm_mul_ps(columns[3], _mm_set1_ps(w)))
+        }
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    #[inline]
+    pub fn mul_mat4_sse2(&self, other: &Self) -> Self {
+        use std::arch::x86_64::_mm_storeu_ps;
+        let columns = self.columns_sse2();
+        let mut data = [0.0; 16];
+        for (column, out) in other.data.chunks_exact(4).zip(data.chunks_exact_mut(4)) {
+            let result =
+                Self::combine_columns_sse2(&columns, column[0], column[1], column[2], column[3]);
+            // Safety: out is 4 f32s long.
+            unsafe { _mm_storeu_ps(out.as_mut_ptr(), result) };
+        }
+        Self { data }
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    #[inline]
+    pub fn mul_vec4_sse2(&self, other: Vec4) -> Vec4 {
+        use std::arch::x86_64::_mm_storeu_ps;
+        let result =
+            Self::combine_columns_sse2(&self.columns_sse2(), other.x, other.y, other.z, other.w);
+        let mut out = [0.0; 4];
+        // Safety: out is 4 f32s long.
+        unsafe { _mm_storeu_ps(out.as_mut_ptr(), result) };
+        Vec4::from(out)
+    }
+}
+
 impl Add for Mat4 {
     type Output = Self;
 
@@ -175,29 +443,12 @@ impl SubAssign for Mat4 {
 impl Mul for Mat4 {
     type Output = Self;
 
-    #[rustfmt::skip]
+    #[inline]
     fn mul(self, other: Self) -> Self {
-        let mut data = std::mem::MaybeUninit::<[f32; 16]>::uninit();
-        let data_p = data.as_mut_ptr() as *mut f32;
-
-        for column in 0..4 {
-            for row in 0..4 {
-                let i = (column * 4) + row;
-                unsafe {
-                    *data_p.add(i) =
-                          self.get(row, 0) * other.get(0, column)
-                        + self.get(row, 1) * other.get(1, column)
-                        + self.get(row, 2) * other.get(2, column)
-                        + self.get(row, 3) * other.get(3, column);
-                }
-            }
-        }
-
-        unsafe {
-            Self {
-                data: data.assume_init(),
-            }
-        }
+        #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+        return self.mul_mat4_sse2(&other);
+        #[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
+        return self.mul_mat4_scalar(&other);
     }
 }
 
@@ -247,25 +498,37 @@ impl Mul<Vec3> for Mat4 {
 impl Mul<Vec4> for Mat4 {
     type Output = Vec4;
 
+    #[inline]
     fn mul(self, other: Vec4) -> Vec4 {
-        Vec4::new(
-            (self.get(0, 0) * other.x)
-                + (self.get(0, 1) * other.y)
-                + (self.get(0, 2) * other.z)
-                + (self.get(0, 3) * other.w),
-            (self.get(1, 0) * other.x)
-                + (self.get(1, 1) * other.y)
-                + (self.get(1, 2) * other.z)
-                + (self.get(1, 3) * other.w),
-            (self.get(2, 0) * other.x)
-                + (self.get(2, 1) * other.y)
-                + (self.get(2, 2) * other.z)
-                + (self.get(2, 3) * other.w),
-            (self.get(3, 0) * other.x)
-                + (self.get(3, 1) * other.y)
-                + (self.get(3, 2) * other.z)
-                + (self.get(3, 3) * other.w),
-        )
+        #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+        return self.mul_vec4_sse2(other);
+        #[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
+        return self.mul_vec4_scalar(other);
+    }
+}
+
+// A row vector times a matrix, which is the same as the transposed matrix times the vector.
+impl Mul<Mat4> for Vec4 {
+    type Output = Vec4;
+
+    fn mul(self, other: Mat4) -> Vec4 {
+        other.transpose() * self
+    }
+}
+
+#[cfg(test)]
+fn assert_mat4_near(a: Mat4, b: Mat4) {
+    for row in 0..4 {
+        for column in 0..4 {
+            assert!(
+                (a.get(row, column) - b.get(row, column)).abs() < 1e-5,
+                "{:?} != {:?} at {} {}",
+                a,
+                b,
+                row,
+                column
+            );
+        }
     }
 }
 
@@ -308,13 +571,13 @@ fn test_mat4_simple_ops_add_sub_f32_mul() {
     assert_eq!(b, b - b + b);
     assert_eq!(Mat4::zero(), b - b);
 
-    let mut c = b.clone();
+    let mut c = b;
 
     c -= b;
 
     assert_eq!(c, Mat4::zero());
 
-    let mut c = b.clone();
+    let mut c = b;
 
     c += b;
     b *= 2.0;
@@ -405,3 +668,163 @@ fn test_mat4_transpose() {
     );
     assert_eq!(a.transpose(), b);
 }
+
+#[test]
+fn test_mat4_determinant() {
+    assert_eq!(Mat4::identity().determinant(), 1.0);
+    assert_eq!(Mat4::scale(2.0, 3.0, 4.0).determinant(), 24.0);
+    assert_eq!(Mat4::zero().determinant(), 0.0);
+
+    #[rustfmt::skip]
+    let a = Mat4::new(
+	3.0, 1.0, 2.0, 2.0,
+	3.0, 1.0, 2.0, 3.0,
+	2.0, 0.0, 1.0, 3.0,
+	1.0, 1.0, 0.0, 1.0,
+    );
+    assert_eq!(a.determinant(), 2.0);
+    assert_eq!(a.transpose().determinant(), 2.0);
+}
+
+#[test]
+fn test_mat4_inverse() {
+    #[rustfmt::skip]
+    let a = Mat4::new(
+	3.0, 1.0, 2.0, 2.0,
+	3.0, 1.0, 2.0, 3.0,
+	2.0, 0.0, 1.0, 3.0,
+	1.0, 1.0, 0.0, 1.0,
+    );
+    let inverse = a.inverse().unwrap();
+    assert_mat4_near(a * inverse, Mat4::identity());
+    assert_mat4_near(inverse * a, Mat4::identity());
+
+    assert_mat4_near(
+        Mat4::translate(1.0, 2.0, 3.0).inverse().unwrap(),
+        Mat4::translate(-1.0, -2.0, -3.0),
+    );
+    assert_mat4_near(
+        Mat4::rotate_y(30.0).inverse().unwrap(),
+        Mat4::rotate_y(30.0).transpose(),
+    );
+
+    // Singular matrices have no inverse.
+    assert_eq!(Mat4::zero().inverse(), None);
+    assert_eq!(Mat4::scale(1.0, 0.0, 1.0).inverse(), None);
+}
+
+#[test]
+fn test_mat4_rotate_axis() {
+    assert_mat4_near(
+        Mat4::rotate_axis(Vec3::new(2.0, 0.0, 0.0), 30.0),
+        Mat4::rotate_x(30.0),
+    );
+    assert_mat4_near(
+        Mat4::rotate_axis(Vec3::new(0.0, 1.0, 0.0), -45.0),
+        Mat4::rotate_y(-45.0),
+    );
+    assert_mat4_near(
+        Mat4::rotate_axis(Vec3::new(0.0, 0.0, 1.0), 90.0),
+        Mat4::rotate_z(90.0),
+    );
+
+    // A third of a turn around the diagonal moves x to y.
+    let p = Mat4::rotate_axis(Vec3::new(1.0, 1.0, 1.0), 120.0) * Vec3::new(1.0, 0.0, 0.0);
+    assert!((p - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-6);
+}
+
+#[test]
+fn test_mat4_look_at() {
+    let view = Mat4::look_at(
+        Vec3::new(1.0, 2.0, 3.0),
+        Vec3::new(1.0, 2.0, 13.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+    // Looking along +z already, so only the eye moves to the origin.
+    assert_mat4_near(view, Mat4::translate(-1.0, -2.0, -3.0));
+
+    // The target ends up straight ahead of the camera.
+    let eye = Vec3::new(2.0, 1.5, -3.5);
+    let view = Mat4::look_at(eye, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let target = view * Vec3::new(0.0, 0.0, 0.0);
+    assert!((target - Vec3::new(0.0, 0.0, eye.len())).len() < 1e-5);
+    assert!((view.determinant() - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn test_mat4_perspective() {
+    let projection = Mat4::perspective(90.0, 2.0, 1.0, 11.0);
+
+    // With a 90 degree field of view, the top of the view is as far up as it is far away.
+    let p = projection.transform_point(Vec3::new(4.0, 2.0, 2.0));
+    assert!((p - Vec3::new(1.0, -1.0, 0.55)).len() < 1e-5);
+    assert!((projection.transform_point(Vec3::new(0.0, 0.0, 1.0)).z).abs() < 1e-6);
+    assert!((projection.transform_point(Vec3::new(0.0, 0.0, 11.0)).z - 1.0).abs() < 1e-6);
+    assert_eq!((projection * Vec4::new(0.0, 0.0, 5.0, 1.0)).w, 5.0);
+}
+
+#[test]
+fn test_mat4_orthographic() {
+    let projection = Mat4::orthographic(-1.0, 3.0, -2.0, 2.0, 1.0, 5.0);
+    let p = projection.transform_point(Vec3::new(3.0, 2.0, 1.0));
+    assert!((p - Vec3::new(1.0, -1.0, 0.0)).len() < 1e-6);
+    let p = projection.transform_point(Vec3::new(-1.0, -2.0, 5.0));
+    assert!((p - Vec3::new(-1.0, 1.0, 1.0)).len() < 1e-6);
+    assert_eq!((projection * Vec4::new(1.0, 1.0, 1.0, 1.0)).w, 1.0);
+}
+
+#[test]
+fn test_mat4_transform_point_vector() {
+    let a = Mat4::translate(1.0, 2.0, 3.0) * Mat4::scale(2.0, 2.0, 2.0);
+    assert_eq!(
+        a.transform_point(Vec3::new(1.0, 1.0, 1.0)),
+        Vec3::new(3.0, 4.0, 5.0)
+    );
+    assert_eq!(
+        a.transform_vector(Vec3::new(1.0, 1.0, 1.0)),
+        Vec3::new(2.0, 2.0, 2.0)
+    );
+}
+
+#[test]
+fn test_vec4_mat4_mul() {
+    #[rustfmt::skip]
+    let a = Mat4::new(
+	3.0, 1.0, 2.0, 2.0,
+	3.0, 1.0, 2.0, 3.0,
+	2.0, 0.0, 1.0, 3.0,
+	1.0, 1.0, 0.0, 1.0,
+    );
+    let b = Vec4::new(1.0, 0.0, 3.0, 1.0);
+    assert_eq!(b * a, Vec4::new(10.0, 2.0, 5.0, 12.0));
+}
+
+#[test]
+fn test_mat4_mul_simd_matches_scalar() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut random_mat4 = || {
+        let mut m = Mat4::zero();
+        for row in 0..4 {
+            for column in 0..4 {
+                m.set(row, column, rng.gen_range(-3.0..3.0));
+            }
+        }
+        m
+    };
+
+    for _ in 0..100 {
+        let a = random_mat4();
+        let b = random_mat4();
+        let v = Vec4::new(b.get(0, 0), b.get(1, 1), b.get(2, 2), b.get(3, 3));
+
+        // Bit for bit the same, not just close.
+        assert_eq!(a * b, a.mul_mat4_scalar(&b));
+        assert_eq!(a * v, a.mul_vec4_scalar(v));
+        #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+        {
+            assert_eq!(a.mul_mat4_sse2(&b), a.mul_mat4_scalar(&b));
+            assert_eq!(a.mul_vec4_sse2(v), a.mul_vec4_scalar(v));
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::mesh::{LineParser, MeshLoadError};
+use crate::texture::{Texture, TextureWrap};
+use sdl2::pixels::Color;
+use std::path::Path;
+
+// How the alpha of a face, its opacity times the alpha of its texture, is drawn.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AlphaMode {
+    // Alpha is ignored.
+    Opaque,
+    // Cut-outs: pixels with alpha below the cutoff, from 0.0 to 1.0, are not drawn, and the rest
+    // are opaque.
+    Mask(f32),
+    // The face is blended over what is behind it by its alpha. These faces are drawn after the
+    // opaque ones, furthest first, and do not write depth.
+    Blend,
+}
+
+/// The surface properties of a face, as described by a Wavefront MTL file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Material {
+    pub name: String,
+    // Ka, how much of the ambient light is reflected, relative to the color of the surface
+    pub ambient: Color,
+    // Kd
+    pub diffuse: Color,
+    // Ks
+    pub specular: Color,
+    // Ns, the specular exponent
+    pub shininess: f32,
+    // d, 1.0 is fully opaque
+    pub opacity: f32,
+    // MTL files have no alpha modes. Materials with an opacity below 1.0 are blended.
+    pub alpha_mode: AlphaMode,
+    // map_Kd. When there is no texture, the texture of the mesh is used.
+    pub texture: Option<Texture>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            ambient: Color::RGB(102, 102, 102),
+            diffuse: Color::RGB(200, 200, 200),
+            specular: Color::RGB(0, 0, 0),
+            shininess: 1.0,
+            opacity: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            texture: None,
+        }
+    }
+}
+
+// Texture options (like "-s 1 1 1") come before the file name, which may contain spaces.
+fn texture_file_name(words: &[&str]) -> Option<String> {
+    let mut words = words;
+    while let Some(option) = words.first().filter(|word| word.starts_with('-')) {
+        let arguments = match *option {
+            "-mm" => 2,
+            // u, v and w, where v and w are optional.
+            "-o" | "-s" | "-t" => {
+                1 + words
+                    .iter()
+                    .skip(2)
+                    .take(2)
+                    .take_while(|word| word.parse::<f32>().is_ok())
+                    .count()
+            }
+            _ => 1,
+        };
+        words = words.get(1 + arguments..)?;
+    }
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+// MTL colors are 0.0 to 1.0 floats.
+fn parse_color(parser: &LineParser, words: &[&str], line: &str) -> Result<Color, MeshLoadError> {
+    if words.len() < 4 {
+        return Err(parser.malformed_line(line));
+    }
+    let mut rgb = [0u8; 3];
+    for (channel, word) in rgb.iter_mut().zip(&words[1..4]) {
+        *channel = (parser.parse_f32(word)? * 255.0).clamp(0.0, 255.0).round() as u8;
+    }
+    Ok(Color::RGB(rgb[0], rgb[1], rgb[2]))
+}
+
+/// Parse the materials of an MTL file.
+/// Texture paths are relative to the directory of mtl_file_path.
+pub fn parse_mtl<R: std::io::BufRead>(
+    reader: R,
+    mtl_file_path: &str,
+) -> Result<Vec<Material>, MeshLoadError> {
+    let mtl_dir = Path::new(mtl_file_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let mut materials: Vec<Material> = Vec::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let parser = LineParser {
+            path: mtl_file_path,
+            line: line_index + 1,
+        };
+        let line = line.map_err(|e| MeshLoadError::Io {
+            path: mtl_file_path.to_string(),
+            line: Some(parser.line),
+            source: e,
+        })?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some(&keyword) = words.first() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = words[1..].join(" ");
+            materials.push(Material {
+                name,
+                ..Material::default()
+            });
+            continue;
+        }
+
+        // Everything else describes the material most recently started with newmtl.
+        let Some(material) = materials.last_mut() else {
+            continue;
+        };
+        match keyword {
+            "Ka" => material.ambient = parse_color(&parser, &words, &line)?,
+            "Kd" => material.diffuse = parse_color(&parser, &words, &line)?,
+            "Ks" => material.specular = parse_color(&parser, &words, &line)?,
+            "Ns" | "d" | "Tr" => {
+                let Some(word) = words.get(1) else {
+                    return Err(parser.malformed_line(&line));
+                };
+                let value = parser.parse_f32(word)?;
+                match keyword {
+                    "Ns" => material.shininess = value,
+                    "d" => material.opacity = value,
+                    // Tr is transparency, the inverse of d.
+                    _ => material.opacity = 1.0 - value,
+                }
+                material.alpha_mode = if material.opacity < 1.0 {
+                    AlphaMode::Blend
+                } else {
+                    AlphaMode::Opaque
+                };
+            }
+            "map_Kd" => {
+                let Some(file_name) = texture_file_name(&words[1..]) else {
+                    return Err(parser.malformed_line(&line));
+                };
+                let texture_path = mtl_dir.join(file_name);
+                let texture_path = texture_path.to_string_lossy();
+                let texture = image::open(texture_path.as_ref())
+                    .map_err(|e| MeshLoadError::MissingTexture {
+                        path: texture_path.to_string(),
+                        line: Some(parser.line),
+                        source: e,
+                    })?
+                    .into_rgba8();
+                // Of the texture options, only "-clamp on" is used. Without it the texture repeats.
+                let clamp = words.windows(2).any(|w| w == ["-clamp", "on"]);
+                let wrap = if clamp {
+                    TextureWrap::ClampToEdge
+                } else {
+                    TextureWrap::Repeat
+                };
+                material.texture = Some(Texture::new(texture).with_wrap(wrap, wrap));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+#[test]
+fn test_parse_mtl() {
+    let mtl = "# Two materials\n\
+               newmtl red\n\
+               Ka 0.1 0.0 0.0\n\
+               Kd 1.0 0.0 0.0\n\
+               Ks 0.5 0.5 0.5\n\
+               Ns 32\n\
+               d 0.5\n\
+               \n\
+               newmtl plain\n\
+               Tr 0.25\n\
+               \n\
+               newmtl solid\n\
+               d 1.0\n";
+    let materials = parse_mtl(std::io::Cursor::new(mtl), "test.mtl").unwrap();
+    assert_eq!(materials.len(), 3);
+
+    assert_eq!(materials[0].name, "red");
+    assert_eq!(materials[0].ambient, Color::RGB(26, 0, 0));
+    assert_eq!(materials[0].diffuse, Color::RGB(255, 0, 0));
+    assert_eq!(materials[0].specular, Color::RGB(128, 128, 128));
+    assert_eq!(materials[0].shininess, 32.0);
+    assert_eq!(materials[0].opacity, 0.5);
+    assert_eq!(materials[0].alpha_mode, AlphaMode::Blend);
+    assert_eq!(materials[0].texture, None);
+
+    assert_eq!(materials[1].name, "plain");
+    assert_eq!(materials[1].diffuse, Material::default().diffuse);
+    assert_eq!(materials[1].opacity, 0.75);
+    assert_eq!(materials[1].alpha_mode, AlphaMode::Blend);
+
+    assert_eq!(materials[2].opacity, 1.0);
+    assert_eq!(materials[2].alpha_mode, AlphaMode::Opaque);
+}
+
+#[test]
+fn test_texture_file_name() {
+    assert_eq!(texture_file_name(&["a.png"]), Some(String::from("a.png")));
+    assert_eq!(
+        texture_file_name(&["my", "texture.png"]),
+        Some(String::from("my texture.png"))
+    );
+    assert_eq!(
+        texture_file_name(&["-s", "1", "2", "1", "-clamp", "on", "my", "texture.png"]),
+        Some(String::from("my texture.png"))
+    );
+    assert_eq!(
+        texture_file_name(&["-o", "0.5", "-mm", "0", "1", "a.png"]),
+        Some(String::from("a.png"))
+    );
+    assert_eq!(texture_file_name(&[]), None);
+    assert_eq!(texture_file_name(&["-clamp", "on"]), None);
+    assert_eq!(texture_file_name(&["-s", "1"]), None);
+}
+
+#[test]
+fn test_parse_mtl_errors() {
+    match parse_mtl(std::io::Cursor::new("newmtl a\nKd 1.0 x 0.0\n"), "test.mtl") {
+        Err(MeshLoadError::ParseNumber { path, line, text }) => {
+            assert_eq!(path, "test.mtl");
+            assert_eq!(line, 2);
+            assert_eq!(text, "x");
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match parse_mtl(
+        std::io::Cursor::new("newmtl a\nmap_Kd no_such_file.png\n"),
+        "./assets/test.mtl",
+    ) {
+        Err(MeshLoadError::MissingTexture { path, line, .. }) => {
+            assert_eq!(path, "./assets/no_such_file.png");
+            assert_eq!(line, Some(2));
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
@@ -1,6 +1,10 @@
+use crate::material::{parse_mtl, Material};
+use crate::texture::{Texture, TextureWrap};
 use crate::vec::{Vec2, Vec3};
-use image::{ImageBuffer, Rgb};
+use image::{ImageBuffer, Rgba};
 use sdl2::pixels::Color;
+use std::collections::HashMap;
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Face {
@@ -10,7 +14,11 @@ pub struct Face {
     pub a_uv: usize,
     pub b_uv: usize,
     pub c_uv: usize,
-    pub color: Color,
+    pub a_normal: usize,
+    pub b_normal: usize,
+    pub c_normal: usize,
+    // An index into the materials of the mesh.
+    pub material: usize,
 }
 
 impl Face {
@@ -22,74 +30,1017 @@ impl Face {
             a_uv,
             b_uv,
             c_uv,
-            color: Color::RGB(200, 200, 200),
+            // By default each vertex has its own normal, with the same index as the vertex.
+            a_normal: a,
+            b_normal: b,
+            c_normal: c,
+            material: 0,
         }
     }
 }
 
+#[derive(Debug)]
+pub enum MeshLoadError {
+    Io {
+        path: String,
+        line: Option<usize>,
+        source: std::io::Error,
+    },
+    ParseNumber {
+        path: String,
+        line: usize,
+        text: String,
+    },
+    MalformedLine {
+        path: String,
+        line: usize,
+        text: String,
+    },
+    ZeroIndex {
+        path: String,
+        line: usize,
+    },
+    IndexOutOfRange {
+        path: String,
+        line: usize,
+        index: i64,
+        len: usize,
+    },
+    MissingTexture {
+        path: String,
+        line: Option<usize>,
+        source: image::ImageError,
+    },
+    // Errors from reading a glTF file, which are reported by the gltf crate.
+    Gltf {
+        path: String,
+        message: String,
+    },
+    // Problems in files that are not line based (binary STL and PLY), or that cannot be tied to
+    // a single line.
+    InvalidData {
+        path: String,
+        message: String,
+    },
+}
+
+// Formats "path" or "path:line", the way compilers report locations.
+fn location(path: &str, line: Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", path, line),
+        None => path.to_string(),
+    }
+}
+
+impl fmt::Display for MeshLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshLoadError::Io { path, line, source } => {
+                write!(f, "{}: I/O error: {}", location(path, *line), source)
+            }
+            MeshLoadError::ParseNumber { path, line, text } => {
+                write!(f, "{}:{}: could not parse number {:?}", path, line, text)
+            }
+            MeshLoadError::MalformedLine { path, line, text } => {
+                write!(f, "{}:{}: malformed line {:?}", path, line, text)
+            }
+            MeshLoadError::ZeroIndex { path, line } => {
+                write!(
+                    f,
+                    "{}:{}: index 0 is invalid, indices start at 1",
+                    path, line
+                )
+            }
+            MeshLoadError::IndexOutOfRange {
+                path,
+                line,
+                index,
+                len,
+            } => write!(
+                f,
+                "{}:{}: index {} is out of range, only {} elements are defined",
+                path, line, index, len
+            ),
+            MeshLoadError::MissingTexture { path, line, source } => {
+                write!(
+                    f,
+                    "{}: could not load texture: {}",
+                    location(path, *line),
+                    source
+                )
+            }
+            MeshLoadError::Gltf { path, message } => write!(f, "{}: {}", path, message),
+            MeshLoadError::InvalidData { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for MeshLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MeshLoadError::Io { source, .. } => Some(source),
+            MeshLoadError::MissingTexture { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Parses one line of an OBJ or MTL file. Line numbers are 1-indexed, like in a text editor.
+pub(crate) struct LineParser<'a> {
+    pub(crate) path: &'a str,
+    pub(crate) line: usize,
+}
+
+impl LineParser<'_> {
+    pub(crate) fn parse_f32(&self, text: &str) -> Result<f32, MeshLoadError> {
+        text.parse().map_err(|_| MeshLoadError::ParseNumber {
+            path: self.path.to_string(),
+            line: self.line,
+            text: text.to_string(),
+        })
+    }
+
+    pub(crate) fn malformed_line(&self, text: &str) -> MeshLoadError {
+        MeshLoadError::MalformedLine {
+            path: self.path.to_string(),
+            line: self.line,
+            text: text.to_string(),
+        }
+    }
+
+    // Converts a 1-indexed OBJ index into a 0-indexed index into a list of len elements.
+    // Negative indices are relative to the end of the list: -1 is the last element defined so far.
+    fn parse_index(&self, text: &str, len: usize) -> Result<usize, MeshLoadError> {
+        let index: i64 = text.parse().map_err(|_| MeshLoadError::ParseNumber {
+            path: self.path.to_string(),
+            line: self.line,
+            text: text.to_string(),
+        })?;
+        if index == 0 {
+            return Err(MeshLoadError::ZeroIndex {
+                path: self.path.to_string(),
+                line: self.line,
+            });
+        }
+        // The obj file is 1-indexed. Rust is 0-indexed. Subtract 1 to adjust.
+        let resolved = if index > 0 {
+            index - 1
+        } else {
+            len as i64 + index
+        };
+        if resolved < 0 || resolved >= len as i64 {
+            return Err(MeshLoadError::IndexOutOfRange {
+                path: self.path.to_string(),
+                line: self.line,
+                index,
+                len,
+            });
+        }
+        Ok(resolved as usize)
+    }
+}
+
+/// The geometry read from an OBJ file, before a texture is attached.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjGeometry {
+    pub vertices: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub normals: Vec<Vec3>,
+    pub faces: Vec<Face>,
+    // The first material is the default material, used by faces that do not name a material.
+    pub materials: Vec<Material>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Mesh {
     pub vertices: Vec<Vec3>,
     pub uvs: Vec<Vec2>,
+    pub normals: Vec<Vec3>,
     pub faces: Vec<Face>,
-    pub texture: ImageBuffer<Rgb<u8>, Vec<u8>>,
-    pub rotation: Vec3,
+    // The first material is the default material, used by faces that do not name a material.
+    pub materials: Vec<Material>,
+    // The texture used by materials that do not have their own texture.
+    pub texture: Texture,
+    // Per vertex colors, indexed like the vertices. Empty when the file has no vertex colors.
+    pub colors: Vec<Color>,
+}
+
+/// Whether STL and PLY files are written as text or binary.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    Ascii,
+    Binary,
+}
+
+/// A 1x1 texture in the diffuse color of the default material, for meshes without a texture.
+pub fn default_texture() -> Texture {
+    let color = Material::default().diffuse;
+    Texture::new(ImageBuffer::from_pixel(
+        1,
+        1,
+        Rgba([color.r, color.g, color.b, 255]),
+    ))
 }
 
 impl Mesh {
-    pub fn load_mesh(obj_file_path: &str, texture_file_path: &str) -> Self {
+    pub fn load_mesh(obj_file_path: &str, texture_file_path: &str) -> Result<Self, MeshLoadError> {
         use std::fs::File;
-        use std::io::{BufRead, BufReader};
+        use std::io::BufReader;
+
+        let file = File::open(obj_file_path).map_err(|e| MeshLoadError::Io {
+            path: obj_file_path.to_string(),
+            line: None,
+            source: e,
+        })?;
+        let geometry = Mesh::parse_obj(BufReader::new(file), obj_file_path)?;
+
+        let texture = image::open(texture_file_path)
+            .map_err(|e| MeshLoadError::MissingTexture {
+                path: texture_file_path.to_string(),
+                line: None,
+                source: e,
+            })?
+            .into_rgba8();
+        let texture = Texture::new(texture);
 
+        Ok(Mesh {
+            vertices: geometry.vertices,
+            uvs: geometry.uvs,
+            normals: geometry.normals,
+            faces: geometry.faces,
+            materials: geometry.materials,
+            texture,
+            colors: Vec::new(),
+        })
+    }
+
+    /// Set how the textures of the mesh and of all its materials are sampled outside UVs 0 to 1,
+    /// replacing the modes given by the file, as in
+    /// `Mesh::load(path)?.with_texture_wrap(TextureWrap::Repeat, TextureWrap::Repeat)`.
+    pub fn with_texture_wrap(mut self, wrap_u: TextureWrap, wrap_v: TextureWrap) -> Self {
+        self.texture.wrap_u = wrap_u;
+        self.texture.wrap_v = wrap_v;
+        for texture in self.materials.iter_mut().filter_map(|m| m.texture.as_mut()) {
+            texture.wrap_u = wrap_u;
+            texture.wrap_v = wrap_v;
+        }
+        self
+    }
+
+    /// Load an OBJ, STL or PLY file, choosing the format from the file extension.
+    /// OBJ files loaded this way use the textures of their materials and otherwise the default texture.
+    pub fn load(file_path: &str) -> Result<Self, MeshLoadError> {
+        let extension = std::path::Path::new(file_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("obj") => {
+                let file = std::fs::File::open(file_path).map_err(|e| MeshLoadError::Io {
+                    path: file_path.to_string(),
+                    line: None,
+                    source: e,
+                })?;
+                let geometry = Mesh::parse_obj(std::io::BufReader::new(file), file_path)?;
+                Ok(Mesh {
+                    vertices: geometry.vertices,
+                    uvs: geometry.uvs,
+                    normals: geometry.normals,
+                    faces: geometry.faces,
+                    materials: geometry.materials,
+                    texture: default_texture(),
+                    colors: Vec::new(),
+                })
+            }
+            Some("stl") => crate::stl::load_stl(file_path),
+            Some("ply") => crate::ply::load_ply(file_path),
+            _ => Err(MeshLoadError::InvalidData {
+                path: file_path.to_string(),
+                message: String::from("unsupported mesh file extension"),
+            }),
+        }
+    }
+
+    /// Save the mesh, choosing the file format from the file extension (obj, stl or ply).
+    /// STL and PLY files are written in binary.
+    pub fn save(&self, file_path: &str) -> std::io::Result<()> {
+        use std::io::{BufWriter, Write};
+
+        let extension = std::path::Path::new(file_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        if !matches!(extension.as_deref(), Some("obj" | "stl" | "ply")) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("Unsupported mesh file extension: {}", file_path),
+            ));
+        }
+        let mut writer = BufWriter::new(std::fs::File::create(file_path)?);
+        match extension.as_deref() {
+            Some("obj") => self.write_obj(&mut writer)?,
+            Some("stl") => crate::stl::write_stl(self, &mut writer, Encoding::Binary)?,
+            _ => crate::ply::write_ply(self, &mut writer, Encoding::Binary)?,
+        }
+        writer.flush()
+    }
+
+    /// Write the vertices, UVs, normals and faces as OBJ. Materials and colors are not written.
+    pub fn write_obj<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for v in &self.vertices {
+            writeln!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+        }
+        for uv in &self.uvs {
+            writeln!(writer, "vt {} {}", uv.x, uv.y)?;
+        }
+        for n in &self.normals {
+            writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+        // OBJ indices start at 1.
+        for face in &self.faces {
+            writeln!(
+                writer,
+                "f {}/{}/{} {}/{}/{} {}/{}/{}",
+                face.a + 1,
+                face.a_uv + 1,
+                face.a_normal + 1,
+                face.b + 1,
+                face.b_uv + 1,
+                face.b_normal + 1,
+                face.c + 1,
+                face.c_uv + 1,
+                face.c_normal + 1
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Parse the vertices, UVs, normals and faces of an OBJ file.
+    /// Polygons with more than 3 vertices are triangulated.
+    /// Faces without normals get normals generated from the surrounding faces.
+    /// Material libraries (mtllib) are read relative to the directory of obj_file_path.
+    /// A material library that does not exist is skipped, and faces using its materials
+    /// get the default material. Other errors opening a material library are returned.
+    pub fn parse_obj<R: std::io::BufRead>(
+        reader: R,
+        obj_file_path: &str,
+    ) -> Result<ObjGeometry, MeshLoadError> {
         let mut vertices: Vec<Vec3> = Vec::new();
         let mut uvs: Vec<Vec2> = Vec::new();
+        let mut normals: Vec<Vec3> = Vec::new();
         let mut faces: Vec<Face> = Vec::new();
+        let mut materials: Vec<Material> = vec![Material::default()];
+        let mut material_indices: HashMap<String, usize> = HashMap::new();
+        let mut current_material: usize = 0;
+        // Faces without UV coordinates use a default UV, which is added after all UVs in the file
+        // have been read, so that it does not change the meaning of relative (negative) indices.
+        // The same goes for generated normals.
+        let mut needs_default_uv = false;
+        let mut needs_generated_normals = false;
 
-        let file = File::open(obj_file_path).unwrap();
-        let reader = BufReader::new(file);
-
-        for line in reader.lines() {
-            let line = line.unwrap();
+        for (line_index, line) in reader.lines().enumerate() {
+            let parser = LineParser {
+                path: obj_file_path,
+                line: line_index + 1,
+            };
+            let line = line.map_err(|e| MeshLoadError::Io {
+                path: obj_file_path.to_string(),
+                line: Some(parser.line),
+                source: e,
+            })?;
             let words: Vec<&str> = line.split_whitespace().collect();
-            if words.len() == 3 {
-                if words[0] == "vt" {
-                    uvs.push(Vec2::new(
-                        words[1].parse().unwrap(),
-                        words[2].parse().unwrap(),
-                    ));
-                }
-            } else if words.len() == 4 {
-                if words[0] == "v" {
+            match words.first() {
+                Some(&"v") => {
+                    // An optional 4th coordinate (w) is ignored.
+                    if words.len() < 4 {
+                        return Err(parser.malformed_line(&line));
+                    }
                     vertices.push(Vec3::new(
-                        words[1].parse().unwrap(),
-                        words[2].parse().unwrap(),
-                        words[3].parse().unwrap(),
+                        parser.parse_f32(words[1])?,
+                        parser.parse_f32(words[2])?,
+                        parser.parse_f32(words[3])?,
                     ));
                 }
-                if words[0] == "f" {
-                    let mut a_line = words[1].split('/');
-                    let a: usize = a_line.next().unwrap().parse().unwrap();
-                    let a_uv: usize = a_line.next().unwrap().parse().unwrap();
-                    let mut b_line = words[2].split('/');
-                    let b: usize = b_line.next().unwrap().parse().unwrap();
-                    let b_uv: usize = b_line.next().unwrap().parse().unwrap();
-                    let mut c_line = words[3].split('/');
-                    let c: usize = c_line.next().unwrap().parse().unwrap();
-                    let c_uv: usize = c_line.next().unwrap().parse().unwrap();
-                    // The obj file is 1-indexed. Rust is 0-indexed. Subtract 1 to adjust.
-                    faces.push(Face::new(a - 1, b - 1, c - 1, a_uv - 1, b_uv - 1, c_uv - 1));
+                Some(&"vt") => {
+                    // v is optional and defaults to 0. An optional 3rd coordinate (w) is ignored.
+                    if words.len() < 2 {
+                        return Err(parser.malformed_line(&line));
+                    }
+                    let u = parser.parse_f32(words[1])?;
+                    let v = match words.get(2) {
+                        Some(word) => parser.parse_f32(word)?,
+                        None => 0.0,
+                    };
+                    uvs.push(Vec2::new(u, v));
+                }
+                Some(&"vn") => {
+                    if words.len() < 4 {
+                        return Err(parser.malformed_line(&line));
+                    }
+                    normals.push(
+                        Vec3::new(
+                            parser.parse_f32(words[1])?,
+                            parser.parse_f32(words[2])?,
+                            parser.parse_f32(words[3])?,
+                        )
+                        .unit_norm(),
+                    );
                 }
+                Some(&"mtllib") => {
+                    let obj_dir = std::path::Path::new(obj_file_path)
+                        .parent()
+                        .unwrap_or_else(|| std::path::Path::new(""));
+                    for file_name in &words[1..] {
+                        let mtl_path = obj_dir.join(file_name);
+                        let mtl_path = mtl_path.to_string_lossy();
+                        // Models are often shared without their material library, so a
+                        // missing library is not an error. Any other failure to open it is.
+                        let file = match std::fs::File::open(mtl_path.as_ref()) {
+                            Ok(file) => file,
+                            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                            Err(e) => {
+                                return Err(MeshLoadError::Io {
+                                    path: obj_file_path.to_string(),
+                                    line: Some(parser.line),
+                                    source: e,
+                                })
+                            }
+                        };
+                        let reader = std::io::BufReader::new(file);
+                        for material in parse_mtl(reader, &mtl_path)? {
+                            material_indices.insert(material.name.clone(), materials.len());
+                            materials.push(material);
+                        }
+                    }
+                }
+                Some(&"usemtl") => {
+                    let name = words[1..].join(" ");
+                    current_material = material_indices.get(&name).copied().unwrap_or(0);
+                }
+                Some(&"f") => {
+                    if words.len() < 4 {
+                        return Err(parser.malformed_line(&line));
+                    }
+                    let mut corners: Vec<(usize, Option<usize>, Option<usize>)> =
+                        Vec::with_capacity(words.len());
+                    for word in &words[1..] {
+                        // Each corner is one of: v, v/vt, v//vn or v/vt/vn
+                        let parts: Vec<&str> = word.split('/').collect();
+                        if parts.len() > 3 {
+                            return Err(parser.malformed_line(&line));
+                        }
+                        let vertex = parser.parse_index(parts[0], vertices.len())?;
+                        let uv = match parts.get(1) {
+                            Some(part) if !part.is_empty() => {
+                                Some(parser.parse_index(part, uvs.len())?)
+                            }
+                            _ => None,
+                        };
+                        let normal = match parts.get(2) {
+                            Some(part) => Some(parser.parse_index(part, normals.len())?),
+                            None => None,
+                        };
+                        corners.push((vertex, uv, normal));
+                    }
+
+                    let points: Vec<Vec3> = corners.iter().map(|c| vertices[c.0]).collect();
+                    for [a, b, c] in triangulate(&points) {
+                        let (a, a_uv, a_normal) = corners[a];
+                        let (b, b_uv, b_normal) = corners[b];
+                        let (c, c_uv, c_normal) = corners[c];
+                        if a_uv.is_none() || b_uv.is_none() || c_uv.is_none() {
+                            needs_default_uv = true;
+                        }
+                        if a_normal.is_none() || b_normal.is_none() || c_normal.is_none() {
+                            needs_generated_normals = true;
+                        }
+                        faces.push(Face {
+                            material: current_material,
+                            a_normal: a_normal.unwrap_or(usize::MAX),
+                            b_normal: b_normal.unwrap_or(usize::MAX),
+                            c_normal: c_normal.unwrap_or(usize::MAX),
+                            ..Face::new(
+                                a,
+                                b,
+                                c,
+                                a_uv.unwrap_or(usize::MAX),
+                                b_uv.unwrap_or(usize::MAX),
+                                c_uv.unwrap_or(usize::MAX),
+                            )
+                        });
+                    }
+                }
+                _ => {}
             }
         }
 
-        let texture = image::open(texture_file_path).unwrap().into_rgb8();
+        if needs_default_uv {
+            let default_uv = uvs.len();
+            uvs.push(Vec2::new(0.0, 0.0));
+            for face in faces.iter_mut() {
+                for uv in [&mut face.a_uv, &mut face.b_uv, &mut face.c_uv] {
+                    if *uv == usize::MAX {
+                        *uv = default_uv;
+                    }
+                }
+            }
+        }
 
-        Mesh {
+        if needs_generated_normals {
+            // Generated normals are indexed like the vertices, offset by the normals from the file.
+            let normal_offset = normals.len();
+            normals.extend(generate_vertex_normals(&vertices, &faces));
+            for face in faces.iter_mut() {
+                for (normal, vertex) in [
+                    (&mut face.a_normal, face.a),
+                    (&mut face.b_normal, face.b),
+                    (&mut face.c_normal, face.c),
+                ] {
+                    if *normal == usize::MAX {
+                        *normal = normal_offset + vertex;
+                    }
+                }
+            }
+        }
+
+        Ok(ObjGeometry {
             vertices,
             uvs,
+            normals,
             faces,
-            texture,
-            rotation: Vec3::new(0.0, 0.0, 0.0),
+            materials,
+        })
+    }
+}
+
+/// Generate a normal for every vertex by averaging the normals of the faces around it.
+/// Each face normal is weighted by the angle of the face at the vertex, so that the result does
+/// not depend on how the surrounding surface happens to be split into triangles.
+pub fn generate_vertex_normals(vertices: &[Vec3], faces: &[Face]) -> Vec<Vec3> {
+    let mut normals: Vec<Vec3> = vec![Vec3::new(0.0, 0.0, 0.0); vertices.len()];
+    for face in faces {
+        let corners = [face.a, face.b, face.c];
+        let (a, b, c) = (vertices[face.a], vertices[face.b], vertices[face.c]);
+        let face_normal = (b - a).cross(c - a);
+        if face_normal.len() == 0.0 {
+            continue;
+        }
+        let face_normal = face_normal.unit_norm();
+        for i in 0..3 {
+            let p = vertices[corners[i]];
+            let to_next = (vertices[corners[(i + 1) % 3]] - p).unit_norm();
+            let to_prev = (vertices[corners[(i + 2) % 3]] - p).unit_norm();
+            let angle = to_next.dot(to_prev).clamp(-1.0, 1.0).acos();
+            normals[corners[i]] += face_normal * angle;
+        }
+    }
+    for normal in normals.iter_mut() {
+        if normal.len() > 0.0 {
+            *normal = normal.unit_norm();
         }
     }
+    normals
+}
+
+/// Generate UVs for a mesh without them by projecting every vertex onto a sphere around the
+/// center of the bounding box: u goes around the y axis and v from the bottom to the top.
+pub fn generate_spherical_uvs(vertices: &[Vec3]) -> Vec<Vec2> {
+    if vertices.is_empty() {
+        return Vec::new();
+    }
+    let mut min = vertices[0];
+    let mut max = vertices[0];
+    for v in vertices {
+        min = min.min(*v);
+        max = max.max(*v);
+    }
+    let center = (min + max) * 0.5;
+    vertices
+        .iter()
+        .map(|&v| {
+            let d = v - center;
+            if d.len() == 0.0 {
+                return Vec2::new(0.5, 0.5);
+            }
+            let d = d.unit_norm();
+            Vec2::new(
+                0.5 + d.z.atan2(d.x) / (2.0 * std::f32::consts::PI),
+                0.5 + d.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI,
+            )
+        })
+        .collect()
+}
+
+/// Split a polygon into triangles, returning indices into points.
+/// Triangles keep the winding order of the polygon. Convex polygons are split into a fan
+/// around the first point; concave polygons are split by ear clipping.
+pub fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Find the polygon normal with Newell's method, which works for concave polygons.
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+    for i in 0..n {
+        let p = points[i];
+        let q = points[(i + 1) % n];
+        normal.x += (p.y - q.y) * (p.z + q.z);
+        normal.y += (p.z - q.z) * (p.x + q.x);
+        normal.z += (p.x - q.x) * (p.y + q.y);
+    }
+
+    // Project the polygon onto the axis aligned plane it is most parallel to.
+    // The orientation tells us whether the projected polygon winds counterclockwise or clockwise.
+    let (projected, orientation): (Vec<Vec2>, f32) =
+        if normal.z.abs() >= normal.x.abs() && normal.z.abs() >= normal.y.abs() {
+            (
+                points.iter().map(|p| Vec2::new(p.x, p.y)).collect(),
+                normal.z.signum(),
+            )
+        } else if normal.x.abs() >= normal.y.abs() {
+            (
+                points.iter().map(|p| Vec2::new(p.y, p.z)).collect(),
+                normal.x.signum(),
+            )
+        } else {
+            (
+                points.iter().map(|p| Vec2::new(p.z, p.x)).collect(),
+                normal.y.signum(),
+            )
+        };
+    let turn = |a: Vec2, b: Vec2, c: Vec2| (b - a).cross_z(c - b) * orientation;
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles: Vec<[usize; 3]> = Vec::with_capacity(n - 2);
+    'clip: while remaining.len() > 3 {
+        let m = remaining.len();
+        // Start at the second point, so convex polygons become a fan around the first point.
+        for i in (1..=m).map(|i| i % m) {
+            let prev = remaining[(i + m - 1) % m];
+            let cur = remaining[i];
+            let next = remaining[(i + 1) % m];
+            let (a, b, c) = (projected[prev], projected[cur], projected[next]);
+
+            // A reflex or degenerate corner is not an ear.
+            if turn(a, b, c) <= 0.0 {
+                continue;
+            }
+            // An ear has no other points inside it.
+            let contains_point = remaining.iter().any(|&j| {
+                let p = projected[j];
+                j != prev
+                    && j != cur
+                    && j != next
+                    && turn(a, b, p) >= 0.0
+                    && turn(b, c, p) >= 0.0
+                    && turn(c, a, p) >= 0.0
+            });
+            if contains_point {
+                continue;
+            }
+
+            triangles.push([prev, cur, next]);
+            remaining.remove(i);
+            continue 'clip;
+        }
+
+        // No ear was found, the polygon is degenerate (e.g. self-intersecting).
+        // Fall back to a fan of the remaining points.
+        for i in 1..(remaining.len() - 1) {
+            triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+        }
+        return triangles;
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+#[cfg(test)]
+fn parse_obj_str(obj: &str) -> Result<ObjGeometry, MeshLoadError> {
+    Mesh::parse_obj(std::io::Cursor::new(obj), "test.obj")
+}
+
+#[test]
+fn test_parse_obj() {
+    let geometry = parse_obj_str(
+        "# A triangle\nv 0.0 0.0 0.0\nv 1.0 0.0 0.0\nv 0.0 1.0 0.0\nvt 0.0 0.0\nvt 1.0 0.0\nvt 0.0 1.0\nf 1/1 2/2 3/3\n",
+    )
+    .unwrap();
+    assert_eq!(geometry.vertices.len(), 3);
+    assert_eq!(geometry.vertices[1], Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(geometry.uvs.len(), 3);
+    assert_eq!(geometry.uvs[2], Vec2::new(0.0, 1.0));
+    assert_eq!(geometry.faces, vec![Face::new(0, 1, 2, 0, 1, 2)]);
+}
+
+#[test]
+fn test_parse_obj_errors() {
+    let triangle = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n";
+
+    match parse_obj_str("v 0 0 0\nv 1 x 0\n") {
+        Err(MeshLoadError::ParseNumber { path, line, text }) => {
+            assert_eq!(path, "test.obj");
+            assert_eq!(line, 2);
+            assert_eq!(text, "x");
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match parse_obj_str(&format!("{}f 1/1 0/1 3/1\n", triangle)) {
+        Err(MeshLoadError::ZeroIndex { line, .. }) => assert_eq!(line, 5),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match parse_obj_str(&format!("{}f 1/1 2/1 4/1\n", triangle)) {
+        Err(MeshLoadError::IndexOutOfRange {
+            line, index, len, ..
+        }) => {
+            assert_eq!(line, 5);
+            assert_eq!(index, 4);
+            assert_eq!(len, 3);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match parse_obj_str(&format!("{}f 1/1 2/2 3/1\n", triangle)) {
+        Err(MeshLoadError::IndexOutOfRange { index, len, .. }) => {
+            assert_eq!(index, 2);
+            assert_eq!(len, 1);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_load_mesh_errors() {
+    match Mesh::load_mesh("./assets/no_such_file.obj", "./assets/cube.png") {
+        Err(MeshLoadError::Io { path, line, .. }) => {
+            assert_eq!(path, "./assets/no_such_file.obj");
+            assert_eq!(line, None);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match Mesh::load_mesh("./assets/cube.obj", "./assets/no_such_file.png") {
+        Err(e @ MeshLoadError::MissingTexture { .. }) => {
+            assert!(e.to_string().starts_with("./assets/no_such_file.png: "));
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_obj_face_forms() {
+    let vertices = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n";
+
+    // Generated normals come after the normal from the file, in vertex order.
+    let generated_normals = |face: Face| Face {
+        a_normal: face.a + 1,
+        b_normal: face.b + 1,
+        c_normal: face.c + 1,
+        ..face
+    };
+    let file_normals = |face: Face| Face {
+        a_normal: 0,
+        b_normal: 0,
+        c_normal: 0,
+        ..face
+    };
+
+    let geometry = parse_obj_str(&format!("{}f 1 2 3\n", vertices)).unwrap();
+    assert_eq!(geometry.uvs.len(), 4);
+    assert_eq!(geometry.uvs[3], Vec2::new(0.0, 0.0));
+    assert_eq!(geometry.normals.len(), 5);
+    assert_eq!(geometry.normals[1], Vec3::new(0.0, 0.0, 1.0));
+    assert_eq!(
+        geometry.faces,
+        vec![generated_normals(Face::new(0, 1, 2, 3, 3, 3))]
+    );
+
+    let geometry = parse_obj_str(&format!("{}f 1//1 2//1 3//1\n", vertices)).unwrap();
+    assert_eq!(geometry.normals.len(), 1);
+    assert_eq!(
+        geometry.faces,
+        vec![file_normals(Face::new(0, 1, 2, 3, 3, 3))]
+    );
+
+    let geometry = parse_obj_str(&format!("{}f 1/1/1 2/2/1 3/3/1\n", vertices)).unwrap();
+    assert_eq!(geometry.uvs.len(), 3);
+    assert_eq!(
+        geometry.faces,
+        vec![file_normals(Face::new(0, 1, 2, 0, 1, 2))]
+    );
+
+    // Relative indices count back from the last element defined so far.
+    let geometry = parse_obj_str(&format!("{}f -4/-3/-1 -3/-2/-1 -2/-1/-1\n", vertices)).unwrap();
+    assert_eq!(
+        geometry.faces,
+        vec![file_normals(Face::new(0, 1, 2, 0, 1, 2))]
+    );
+
+    // Quads become two triangles.
+    let geometry = parse_obj_str(&format!("{}f 1/1 2/2 3/3 4/1\n", vertices)).unwrap();
+    assert_eq!(
+        geometry.faces,
+        vec![
+            generated_normals(Face::new(0, 1, 2, 0, 1, 2)),
+            generated_normals(Face::new(0, 2, 3, 0, 2, 0))
+        ]
+    );
+
+    match parse_obj_str(&format!("{}f 1 2\n", vertices)) {
+        Err(MeshLoadError::MalformedLine { line, .. }) => assert_eq!(line, 9),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match parse_obj_str(&format!("{}f 1//2 2//1 3//1\n", vertices)) {
+        Err(MeshLoadError::IndexOutOfRange { index, len, .. }) => {
+            assert_eq!(index, 2);
+            assert_eq!(len, 1);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match parse_obj_str(&format!("{}f -5 1 2\n", vertices)) {
+        Err(MeshLoadError::IndexOutOfRange { index, len, .. }) => {
+            assert_eq!(index, -5);
+            assert_eq!(len, 4);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_triangulate() {
+    let convex = [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(2.0, 1.0, 0.0),
+        Vec3::new(1.0, 2.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    ];
+    assert_eq!(triangulate(&convex), vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+
+    // An arrow shape, which is concave at point 1. A fan around point 0 would cover the notch.
+    let concave = [
+        Vec3::new(2.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.5, 0.0),
+        Vec3::new(-2.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.0, 0.0),
+    ];
+    let triangles = triangulate(&concave);
+    assert_eq!(triangles.len(), 2);
+    let mut area = 0.0;
+    for triangle in triangles.iter() {
+        // Every triangle has the same winding as the polygon (counterclockwise, +z).
+        let [a, b, c] = triangle.map(|i| concave[i]);
+        let cross_z = (b - a).cross(c - a).z;
+        assert!(cross_z > 0.0);
+        area += cross_z / 2.0;
+    }
+    // The triangles exactly cover the polygon, which has an area of 1.
+    assert_eq!(area, 1.0);
+
+    // The same polygon in the y-z plane, wound the other way.
+    let flipped: Vec<Vec3> = concave
+        .iter()
+        .rev()
+        .map(|p| Vec3::new(0.0, p.x, p.y))
+        .collect();
+    let triangles = triangulate(&flipped);
+    assert_eq!(triangles.len(), 2);
+    for triangle in triangles.iter() {
+        let [a, b, c] = triangle.map(|i| flipped[i]);
+        assert!((b - a).cross(c - a).x < 0.0);
+    }
+}
+
+#[test]
+fn test_generate_vertex_normals() {
+    // A unit cube corner: three faces meet at the origin, each with a different normal.
+    // The first face is split into two triangles; weighting by angle keeps the normal symmetric.
+    let vertices = [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(1.0, 0.0, 1.0),
+        Vec3::new(0.0, 1.0, 1.0),
+    ];
+    let faces = [
+        Face::new(0, 3, 2, 0, 0, 0),
+        Face::new(0, 2, 1, 0, 0, 0),
+        Face::new(0, 1, 5, 0, 0, 0),
+        Face::new(0, 5, 4, 0, 0, 0),
+        Face::new(0, 4, 6, 0, 0, 0),
+        Face::new(0, 6, 3, 0, 0, 0),
+    ];
+    let normals = generate_vertex_normals(&vertices, &faces);
+    assert_eq!(normals.len(), vertices.len());
+    let corner = normals[0];
+    let expected = Vec3::new(-1.0, -1.0, -1.0).unit_norm();
+    assert!((corner - expected).len() < 0.000_1, "{:?}", corner);
+    // A vertex on a single flat face gets the face normal.
+    assert_eq!(normals[2], Vec3::new(0.0, 0.0, -1.0));
+}
+
+#[test]
+fn test_parse_obj_materials() {
+    let dir = std::env::temp_dir().join("test_parse_obj_materials");
+    std::fs::create_dir_all(&dir).unwrap();
+    let texture: ImageBuffer<Rgba<u8>, Vec<u8>> =
+        ImageBuffer::from_pixel(2, 2, Rgba([1, 2, 3, 255]));
+    texture.save(dir.join("blue.png")).unwrap();
+    std::fs::write(
+        dir.join("test.mtl"),
+        "newmtl red\nKd 1 0 0\nmap_Kd -clamp on blue.png\n\n\
+         newmtl blue textured\nKd 0 0 1\nmap_Kd blue.png\n",
+    )
+    .unwrap();
+    let obj = "mtllib test.mtl missing.mtl\n\
+               v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+               f 1 2 3\n\
+               usemtl blue textured\nf 1 2 3\n\
+               usemtl red\nf 1 2 3\n\
+               usemtl unknown\nf 1 2 3\n";
+    let obj_path = dir.join("test.obj");
+    let geometry = Mesh::parse_obj(std::io::Cursor::new(obj), obj_path.to_str().unwrap()).unwrap();
+
+    assert_eq!(geometry.materials.len(), 3);
+    assert_eq!(geometry.materials[0], Material::default());
+    assert_eq!(geometry.materials[1].name, "red");
+    assert_eq!(geometry.materials[2].name, "blue textured");
+    // MTL textures repeat unless they are clamped.
+    let texture = Texture::new(texture);
+    assert_eq!(
+        geometry.materials[1].texture,
+        Some(
+            texture
+                .clone()
+                .with_wrap(TextureWrap::ClampToEdge, TextureWrap::ClampToEdge)
+        )
+    );
+    assert_eq!(
+        geometry.materials[2].texture,
+        Some(texture.with_wrap(TextureWrap::Repeat, TextureWrap::Repeat))
+    );
+
+    let face_materials: Vec<usize> = geometry.faces.iter().map(|f| f.material).collect();
+    // missing.mtl does not exist, so it is skipped and "unknown" gets the default material.
+    assert!(!dir.join("missing.mtl").exists());
+    assert_eq!(face_materials, vec![0, 2, 1, 0]);
+
+    // Other errors opening a library are returned, here because blue.png is not a directory.
+    let obj = "v 0 0 0\nmtllib blue.png/test.mtl\n";
+    match Mesh::parse_obj(std::io::Cursor::new(obj), obj_path.to_str().unwrap()) {
+        Err(MeshLoadError::Io { path, line, .. }) => {
+            assert_eq!(path, obj_path.to_str().unwrap());
+            assert_eq!(line, Some(2));
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_mesh_save_load() {
+    let cube = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap();
+
+    let dir = std::env::temp_dir();
+    for extension in ["obj", "stl", "ply"] {
+        let file_path = dir.join(format!("test_mesh_save_load.{}", extension));
+        let file_path = file_path.to_str().unwrap();
+        cube.save(file_path).unwrap();
+        let mesh = Mesh::load(file_path).unwrap();
+        std::fs::remove_file(file_path).unwrap();
+
+        assert_eq!(mesh.faces.len(), cube.faces.len());
+        for (face, cube_face) in mesh.faces.iter().zip(cube.faces.iter()) {
+            assert_eq!(mesh.vertices[face.a], cube.vertices[cube_face.a]);
+            assert_eq!(mesh.vertices[face.b], cube.vertices[cube_face.b]);
+            assert_eq!(mesh.vertices[face.c], cube.vertices[cube_face.c]);
+        }
+    }
+
+    // OBJ keeps the UV and normal indices as they are.
+    let file_path = dir.join("test_mesh_save_load_indices.obj");
+    let file_path = file_path.to_str().unwrap();
+    cube.save(file_path).unwrap();
+    let mesh = Mesh::load(file_path).unwrap();
+    std::fs::remove_file(file_path).unwrap();
+    assert_eq!(mesh.faces, cube.faces);
+    assert_eq!(mesh.uvs, cube.uvs);
+    assert_eq!(mesh.normals, cube.normals);
+
+    assert!(cube.save("mesh.fbx").is_err());
+    assert!(matches!(
+        Mesh::load("mesh.fbx"),
+        Err(MeshLoadError::InvalidData { .. })
+    ));
 }
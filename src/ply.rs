@@ -0,0 +1,592 @@
+// PLY files start with a text header that declares the elements of the file (vertex, face, ...)
+// and the properties of each element, followed by the element data in ASCII or binary.
+use crate::material::Material;
+use crate::mesh::{
+    default_texture, generate_spherical_uvs, generate_vertex_normals, triangulate, Encoding, Face,
+    LineParser, Mesh, MeshLoadError,
+};
+use crate::vec::{Vec2, Vec3};
+use sdl2::pixels::Color;
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        self == ScalarType::Float32 || self == ScalarType::Float64
+    }
+}
+
+struct Property {
+    name: String,
+    value_type: ScalarType,
+    // List properties start with a count of this type, followed by that many values.
+    count_type: Option<ScalarType>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid_data(file_path: &str, message: String) -> MeshLoadError {
+    MeshLoadError::InvalidData {
+        path: file_path.to_string(),
+        message,
+    }
+}
+
+// Reads the values of the elements after the header, one at a time.
+struct ValueReader<'a> {
+    path: &'a str,
+    format: Format,
+    // The binary data that has not been read yet.
+    bytes: &'a [u8],
+    // The ASCII words that have not been read yet, with their line numbers.
+    words: std::vec::IntoIter<(usize, &'a str)>,
+}
+
+impl ValueReader<'_> {
+    fn read(&mut self, value_type: ScalarType) -> Result<f64, MeshLoadError> {
+        if self.format == Format::Ascii {
+            let (line, word) = self
+                .words
+                .next()
+                .ok_or_else(|| invalid_data(self.path, String::from("unexpected end of file")))?;
+            return word.parse().map_err(|_| MeshLoadError::ParseNumber {
+                path: self.path.to_string(),
+                line,
+                text: word.to_string(),
+            });
+        }
+
+        let size = value_type.size();
+        if self.bytes.len() < size {
+            return Err(invalid_data(
+                self.path,
+                String::from("unexpected end of file"),
+            ));
+        }
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(&self.bytes[..size]);
+        self.bytes = &self.bytes[size..];
+        if self.format == Format::BinaryBigEndian {
+            b[..size].reverse();
+        }
+        Ok(match value_type {
+            ScalarType::Int8 => b[0] as i8 as f64,
+            ScalarType::UInt8 => b[0] as f64,
+            ScalarType::Int16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            ScalarType::UInt16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            ScalarType::Int32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::UInt32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::Float64 => f64::from_le_bytes(b),
+        })
+    }
+}
+
+/// Load an ASCII or binary PLY file.
+/// Vertex positions, normals, UVs and colors are read from the vertex element, and polygons
+/// from the face element. Polygons are triangulated. Missing normals are generated from the faces
+/// and missing UVs with a spherical projection.
+pub fn load_ply(file_path: &str) -> Result<Mesh, MeshLoadError> {
+    let bytes = std::fs::read(file_path).map_err(|e| MeshLoadError::Io {
+        path: file_path.to_string(),
+        line: None,
+        source: e,
+    })?;
+    parse_ply(&bytes, file_path)
+}
+
+fn parse_header(header: &str, file_path: &str) -> Result<(Format, Vec<Element>), MeshLoadError> {
+    let mut format: Option<Format> = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for (line_index, line) in header.lines().enumerate() {
+        let parser = LineParser {
+            path: file_path,
+            line: line_index + 1,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["ply"] if line_index == 0 => {}
+            _ if line_index == 0 => {
+                return Err(invalid_data(file_path, String::from("not a PLY file")))
+            }
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(parser.malformed_line(line)),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| MeshLoadError::ParseNumber {
+                    path: file_path.to_string(),
+                    line: parser.line,
+                    text: count.to_string(),
+                })?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, value_type, name] => {
+                let (Some(count_type), Some(value_type), Some(element)) = (
+                    ScalarType::parse(count_type),
+                    ScalarType::parse(value_type),
+                    elements.last_mut(),
+                ) else {
+                    return Err(parser.malformed_line(line));
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    value_type,
+                    count_type: Some(count_type),
+                });
+            }
+            ["property", value_type, name] => {
+                let (Some(value_type), Some(element)) =
+                    (ScalarType::parse(value_type), elements.last_mut())
+                else {
+                    return Err(parser.malformed_line(line));
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    value_type,
+                    count_type: None,
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] | ["end_header"] | [] => {}
+            _ => return Err(parser.malformed_line(line)),
+        }
+    }
+
+    let format =
+        format.ok_or_else(|| invalid_data(file_path, String::from("the header has no format")))?;
+    Ok((format, elements))
+}
+
+pub fn parse_ply(bytes: &[u8], file_path: &str) -> Result<Mesh, MeshLoadError> {
+    // The header is text, and ends with the line "end_header".
+    let header_end = bytes
+        .windows(11)
+        .position(|w| w == b"end_header\n" || w == b"end_header\r")
+        .ok_or_else(|| invalid_data(file_path, String::from("the header has no end_header")))?;
+    let body_start = match bytes.get(header_end + 10..header_end + 12) {
+        Some(b"\r\n") => header_end + 12,
+        _ => header_end + 11,
+    };
+    let header = String::from_utf8_lossy(&bytes[..body_start]);
+    let (format, elements) = parse_header(&header, file_path)?;
+
+    let body_text;
+    let mut reader = ValueReader {
+        path: file_path,
+        format,
+        bytes: &bytes[body_start..],
+        words: Vec::new().into_iter(),
+    };
+    if format == Format::Ascii {
+        body_text = String::from_utf8_lossy(&bytes[body_start..]);
+        let first_line = header.lines().count() + 1;
+        reader.words = body_text
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| line.split_whitespace().map(move |w| (first_line + i, w)))
+            .collect::<Vec<_>>()
+            .into_iter();
+    }
+
+    let mut vertices: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut colors: Vec<Color> = Vec::new();
+    let mut polygons: Vec<Vec<usize>> = Vec::new();
+
+    for element in &elements {
+        let find = |names: &[&str]| -> Option<usize> {
+            element
+                .properties
+                .iter()
+                .position(|p| p.count_type.is_none() && names.contains(&p.name.as_str()))
+        };
+        let find_all = |names: [&[&str]; 3]| -> Option<[usize; 3]> {
+            Some([find(names[0])?, find(names[1])?, find(names[2])?])
+        };
+        let position = find_all([&["x"], &["y"], &["z"]]);
+        let normal = find_all([&["nx"], &["ny"], &["nz"]]);
+        let color = find_all([
+            &["red", "diffuse_red"],
+            &["green", "diffuse_green"],
+            &["blue", "diffuse_blue"],
+        ]);
+        let uv = find(&["u", "s", "texture_u", "texture_s"]).zip(find(&[
+            "v",
+            "t",
+            "texture_v",
+            "texture_t",
+        ]));
+        let face_list = element.properties.iter().position(|p| {
+            p.count_type.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index")
+        });
+
+        if element.name == "vertex" && position.is_none() {
+            return Err(invalid_data(
+                file_path,
+                String::from("the vertex element has no x, y and z properties"),
+            ));
+        }
+
+        // Values of scalar properties, indexed like the properties. Lists get a placeholder.
+        let mut values: Vec<f64> = Vec::with_capacity(element.properties.len());
+        let mut list: Vec<usize> = Vec::new();
+        for _ in 0..element.count {
+            values.clear();
+            list.clear();
+            for (i, property) in element.properties.iter().enumerate() {
+                let Some(count_type) = property.count_type else {
+                    values.push(reader.read(property.value_type)?);
+                    continue;
+                };
+                values.push(0.0);
+                let count = reader.read(count_type)? as usize;
+                for _ in 0..count {
+                    let value = reader.read(property.value_type)?;
+                    if Some(i) == face_list {
+                        list.push(value as usize);
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let vec3 = |[x, y, z]: [usize; 3]| {
+                        Vec3::new(values[x] as f32, values[y] as f32, values[z] as f32)
+                    };
+                    vertices.push(vec3(position.unwrap()));
+                    if let Some(normal) = normal {
+                        normals.push(vec3(normal).unit_norm());
+                    }
+                    if let Some((u, v)) = uv {
+                        uvs.push(Vec2::new(values[u] as f32, values[v] as f32));
+                    }
+                    if let Some(color) = color {
+                        // Colors are usually 0 to 255, but float colors are 0.0 to 1.0.
+                        let [r, g, b] = color.map(|i| {
+                            let scale = if element.properties[i].value_type.is_float() {
+                                255.0
+                            } else {
+                                1.0
+                            };
+                            (values[i] * scale).clamp(0.0, 255.0).round() as u8
+                        });
+                        colors.push(Color::RGB(r, g, b));
+                    }
+                }
+                "face" if face_list.is_some() => polygons.push(list.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    let mut faces: Vec<Face> = Vec::with_capacity(polygons.len());
+    for polygon in &polygons {
+        if let Some(&index) = polygon.iter().find(|&&i| i >= vertices.len()) {
+            return Err(invalid_data(
+                file_path,
+                format!(
+                    "vertex index {} is out of range, only {} vertices are defined",
+                    index,
+                    vertices.len()
+                ),
+            ));
+        }
+        let points: Vec<Vec3> = polygon.iter().map(|&i| vertices[i]).collect();
+        for [a, b, c] in triangulate(&points) {
+            let (a, b, c) = (polygon[a], polygon[b], polygon[c]);
+            faces.push(Face::new(a, b, c, a, b, c));
+        }
+    }
+
+    if normals.is_empty() {
+        normals = generate_vertex_normals(&vertices, &faces);
+    }
+    if uvs.is_empty() {
+        uvs = generate_spherical_uvs(&vertices);
+    }
+
+    Ok(Mesh {
+        vertices,
+        uvs,
+        normals,
+        faces,
+        materials: vec![Material::default()],
+        texture: default_texture(),
+        colors,
+    })
+}
+
+/// Write the mesh as PLY, with positions, normals, UVs and colors (when the mesh has them).
+/// PLY has one UV and normal per vertex, so vertices used with different UVs or normals are
+/// written once for every combination. Materials are not written.
+pub fn write_ply<W: std::io::Write>(
+    mesh: &Mesh,
+    writer: &mut W,
+    encoding: Encoding,
+) -> std::io::Result<()> {
+    // The vertex, UV and normal indices of every written vertex.
+    let mut corners: Vec<(usize, usize, usize)> = Vec::new();
+    let mut corner_indices: HashMap<(usize, usize, usize), u32> = HashMap::new();
+    let mut corner_index = |corner: (usize, usize, usize)| {
+        *corner_indices.entry(corner).or_insert_with(|| {
+            corners.push(corner);
+            (corners.len() - 1) as u32
+        })
+    };
+    let faces: Vec<[u32; 3]> = mesh
+        .faces
+        .iter()
+        .map(|face| {
+            [
+                corner_index((face.a, face.a_uv, face.a_normal)),
+                corner_index((face.b, face.b_uv, face.b_normal)),
+                corner_index((face.c, face.c_uv, face.c_normal)),
+            ]
+        })
+        .collect();
+    let has_colors = !mesh.colors.is_empty();
+
+    let format = match encoding {
+        Encoding::Ascii => "ascii",
+        Encoding::Binary => "binary_little_endian",
+    };
+    writeln!(writer, "ply\nformat {} 1.0", format)?;
+    writeln!(writer, "comment written by graphics_from_scratch")?;
+    writeln!(writer, "element vertex {}", corners.len())?;
+    for name in ["x", "y", "z", "nx", "ny", "nz", "s", "t"] {
+        writeln!(writer, "property float {}", name)?;
+    }
+    if has_colors {
+        for name in ["red", "green", "blue"] {
+            writeln!(writer, "property uchar {}", name)?;
+        }
+    }
+    writeln!(writer, "element face {}", faces.len())?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for &(vertex, uv, normal) in &corners {
+        let (v, n, uv) = (mesh.vertices[vertex], mesh.normals[normal], mesh.uvs[uv]);
+        let floats = [v.x, v.y, v.z, n.x, n.y, n.z, uv.x, uv.y];
+        let color = mesh.colors.get(vertex).filter(|_| has_colors);
+        match encoding {
+            Encoding::Ascii => {
+                let mut line: Vec<String> = floats.iter().map(|f| f.to_string()).collect();
+                if let Some(c) = color {
+                    line.extend([c.r, c.g, c.b].map(|c| c.to_string()));
+                }
+                writeln!(writer, "{}", line.join(" "))?;
+            }
+            Encoding::Binary => {
+                for f in floats {
+                    writer.write_all(&f.to_le_bytes())?;
+                }
+                if let Some(c) = color {
+                    writer.write_all(&[c.r, c.g, c.b])?;
+                }
+            }
+        }
+    }
+    for [a, b, c] in faces {
+        match encoding {
+            Encoding::Ascii => writeln!(writer, "3 {} {} {}", a, b, c)?,
+            Encoding::Binary => {
+                writer.write_all(&[3])?;
+                for i in [a, b, c] {
+                    writer.write_all(&i.to_le_bytes())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse_ply_ascii() {
+    // A colored quad, without normals or UVs.
+    let ply = "ply\n\
+               format ascii 1.0\n\
+               comment a quad\n\
+               element vertex 4\n\
+               property float x\n\
+               property float y\n\
+               property float z\n\
+               property uchar red\n\
+               property uchar green\n\
+               property uchar blue\n\
+               element face 1\n\
+               property list uchar int vertex_indices\n\
+               end_header\n\
+               0 0 0 255 0 0\n\
+               1 0 0 0 255 0\n\
+               1 1 0 0 0 255\n\
+               0 1 0 10 20 30\n\
+               4 0 1 2 3\n";
+    let mesh = parse_ply(ply.as_bytes(), "test.ply").unwrap();
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.vertices[2], Vec3::new(1.0, 1.0, 0.0));
+    assert_eq!(
+        mesh.faces,
+        vec![Face::new(0, 1, 2, 0, 1, 2), Face::new(0, 2, 3, 0, 2, 3)]
+    );
+    assert_eq!(mesh.colors[0], Color::RGB(255, 0, 0));
+    assert_eq!(mesh.colors[3], Color::RGB(10, 20, 30));
+    assert_eq!(mesh.normals, vec![Vec3::new(0.0, 0.0, 1.0); 4]);
+    assert_eq!(mesh.uvs.len(), 4);
+}
+
+#[test]
+fn test_parse_ply_binary() {
+    // Big endian, with float colors, a vertex property and an element that are not used.
+    let mut ply: Vec<u8> = b"ply\n\
+        format binary_big_endian 1.0\n\
+        element vertex 3\n\
+        property double x\n\
+        property double y\n\
+        property double z\n\
+        property float confidence\n\
+        property float red\n\
+        property float green\n\
+        property float blue\n\
+        element face 1\n\
+        property list uchar ushort vertex_index\n\
+        element edge 1\n\
+        property int vertex1\n\
+        property int vertex2\n\
+        end_header\n"
+        .to_vec();
+    for (x, y) in [(0.0f64, 0.0f64), (2.0, 0.0), (0.0, 2.0)] {
+        for f in [x, y, 5.0] {
+            ply.extend_from_slice(&f.to_be_bytes());
+        }
+        for f in [0.5f32, 1.0, 0.5, 0.0] {
+            ply.extend_from_slice(&f.to_be_bytes());
+        }
+    }
+    ply.push(3);
+    for i in [0u16, 1, 2] {
+        ply.extend_from_slice(&i.to_be_bytes());
+    }
+    for i in [0i32, 1] {
+        ply.extend_from_slice(&i.to_be_bytes());
+    }
+
+    let mesh = parse_ply(&ply, "test.ply").unwrap();
+    assert_eq!(mesh.vertices[1], Vec3::new(2.0, 0.0, 5.0));
+    assert_eq!(mesh.faces, vec![Face::new(0, 1, 2, 0, 1, 2)]);
+    assert_eq!(mesh.colors, vec![Color::RGB(255, 128, 0); 3]);
+}
+
+#[test]
+fn test_ply_round_trip() {
+    let mut cube = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap();
+    cube.colors = (0..cube.vertices.len())
+        .map(|i| Color::RGB(i as u8 * 30, 100, 200))
+        .collect();
+
+    for encoding in [Encoding::Ascii, Encoding::Binary] {
+        let mut bytes: Vec<u8> = Vec::new();
+        write_ply(&cube, &mut bytes, encoding).unwrap();
+        let mesh = parse_ply(&bytes, "test.ply").unwrap();
+
+        assert_eq!(mesh.faces.len(), cube.faces.len());
+        for (face, cube_face) in mesh.faces.iter().zip(cube.faces.iter()) {
+            for (i, cube_i) in [
+                (face.a, (cube_face.a, cube_face.a_uv, cube_face.a_normal)),
+                (face.b, (cube_face.b, cube_face.b_uv, cube_face.b_normal)),
+                (face.c, (cube_face.c, cube_face.c_uv, cube_face.c_normal)),
+            ] {
+                assert_eq!(mesh.vertices[i], cube.vertices[cube_i.0]);
+                assert_eq!(mesh.uvs[i], cube.uvs[cube_i.1]);
+                assert_eq!(mesh.normals[i], cube.normals[cube_i.2]);
+                assert_eq!(mesh.colors[i], cube.colors[cube_i.0]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_ply_errors() {
+    match parse_ply(b"obj\nend_header\n", "test.ply") {
+        Err(MeshLoadError::InvalidData { path, .. }) => assert_eq!(path, "test.ply"),
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.faces)),
+    }
+
+    let header = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\n\
+                  property float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+                  end_header\n";
+    match parse_ply(format!("{}0 0 x\n3 0 0 0\n", header).as_bytes(), "test.ply") {
+        Err(MeshLoadError::ParseNumber { line, text, .. }) => {
+            assert_eq!(line, 10);
+            assert_eq!(text, "x");
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.faces)),
+    }
+
+    match parse_ply(format!("{}0 0 0\n3 0 0 1\n", header).as_bytes(), "test.ply") {
+        Err(e @ MeshLoadError::InvalidData { .. }) => {
+            assert!(e.to_string().contains("vertex index 1 is out of range"))
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.faces)),
+    }
+
+    match parse_ply(
+        b"ply\nformat ascii 1.0\nproperty float x\nend_header\n",
+        "test.ply",
+    ) {
+        Err(MeshLoadError::MalformedLine { line, .. }) => assert_eq!(line, 3),
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.faces)),
+    }
+}
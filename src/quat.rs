@@ -0,0 +1,305 @@
+use crate::mat::Mat4;
+use crate::vec::{Vec3, Vec4};
+use std::ops::Mul;
+
+/// A rotation stored as a unit quaternion. Unlike Euler angles, quaternions have no gimbal lock,
+/// and they can be interpolated smoothly with slerp.
+/// Angles are in degrees, and rotations have the same direction as the Mat4 rotations.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Quat {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quat {
+    pub const fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub const fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// The axis does not need to be normal.
+    pub fn from_axis_angle(axis: Vec3, angle_degrees: f32) -> Self {
+        let axis = axis.unit_norm();
+        let (sin_half, cos_half) = (angle_degrees.to_radians() / 2.0).sin_cos();
+        Self::new(
+            cos_half,
+            axis.x * sin_half,
+            axis.y * sin_half,
+            axis.z * sin_half,
+        )
+    }
+
+    /// The normal axis and the angle in degrees (0 to 360) of the rotation.
+    /// The identity rotation has no axis, so it returns the x axis.
+    pub fn to_axis_angle(&self) -> (Vec3, f32) {
+        let q = self.normalize();
+        let angle_degrees = (2.0 * q.w.clamp(-1.0, 1.0).acos()).to_degrees();
+        let sin_half = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if sin_half < 1e-6 {
+            return (Vec3::new(1.0, 0.0, 0.0), angle_degrees);
+        }
+        (
+            Vec3::new(q.x / sin_half, q.y / sin_half, q.z / sin_half),
+            angle_degrees,
+        )
+    }
+
+    /// The rotation that turns +z towards forward, with +y as close to up as possible.
+    /// This is the orientation of a camera looking along forward.
+    pub fn look_rotation(forward: Vec3, up: Vec3) -> Self {
+        let z_axis = forward.unit_norm();
+        let mut x_axis = up.cross(z_axis);
+        if x_axis.len() < 1e-6 {
+            // Looking straight along up, any x axis at right angles to it will do.
+            x_axis = Vec3::new(1.0, 0.0, 0.0).cross(z_axis);
+            if x_axis.len() < 1e-6 {
+                x_axis = Vec3::new(0.0, 0.0, 1.0).cross(z_axis);
+            }
+        }
+        let x_axis = x_axis.unit_norm();
+        let y_axis = z_axis.cross(x_axis);
+        #[rustfmt::skip]
+        let basis = Mat4::new(
+            x_axis.x, y_axis.x, z_axis.x, 0.0,
+            x_axis.y, y_axis.y, z_axis.y, 0.0,
+            x_axis.z, y_axis.z, z_axis.z, 0.0,
+            0.0,      0.0,      0.0,      1.0,
+        );
+        Self::from_mat4(&basis)
+    }
+
+    /// The rotation of the top left 3x3 part of the matrix, which must be a rotation matrix
+    /// (orthonormal, no scale).
+    pub fn from_mat4(m: &Mat4) -> Self {
+        // Find the largest component first, and the others from it, to avoid dividing by
+        // small numbers.
+        let m = |row: usize, column: usize| m.get(row, column);
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new(
+                s / 4.0,
+                (m(2, 1) - m(1, 2)) / s,
+                (m(0, 2) - m(2, 0)) / s,
+                (m(1, 0) - m(0, 1)) / s,
+            )
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.0;
+            Self::new(
+                (m(2, 1) - m(1, 2)) / s,
+                s / 4.0,
+                (m(0, 1) + m(1, 0)) / s,
+                (m(0, 2) + m(2, 0)) / s,
+            )
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.0;
+            Self::new(
+                (m(0, 2) - m(2, 0)) / s,
+                (m(0, 1) + m(1, 0)) / s,
+                s / 4.0,
+                (m(1, 2) + m(2, 1)) / s,
+            )
+        } else {
+            let s = (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.0;
+            Self::new(
+                (m(1, 0) - m(0, 1)) / s,
+                (m(0, 2) + m(2, 0)) / s,
+                (m(1, 2) + m(2, 1)) / s,
+                s / 4.0,
+            )
+        };
+        q.normalize()
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        let Quat { w, x, y, z } = *self;
+        #[rustfmt::skip]
+        let m = Mat4::new(
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z),       2.0 * (x * z + w * y),       0.0,
+            2.0 * (x * y + w * z),       1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x),       0.0,
+            2.0 * (x * z - w * y),       2.0 * (y * z + w * x),       1.0 - 2.0 * (x * x + y * y), 0.0,
+            0.0,                         0.0,                         0.0,                         1.0,
+        );
+        m
+    }
+
+    pub fn len(&self) -> f32 {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn dot(&self, other: Self) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Rotations drift away from unit length as they are multiplied together. Normalizing brings
+    /// them back.
+    pub fn normalize(&self) -> Self {
+        let len = self.len();
+        Self::new(self.w / len, self.x / len, self.y / len, self.z / len)
+    }
+
+    /// The inverse rotation, for unit quaternions.
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Spherical linear interpolation: rotates from self (t = 0.0) to other (t = 1.0) at a constant
+    /// speed, the short way around.
+    pub fn slerp(&self, other: Self, t: f32) -> Self {
+        // q and -q are the same rotation. Pick the one closest to self to take the short way.
+        let mut dot = self.dot(other);
+        let other = if dot < 0.0 {
+            dot = -dot;
+            Self::new(-other.w, -other.x, -other.y, -other.z)
+        } else {
+            other
+        };
+
+        // Nearly the same rotation. The angle is too small to divide by, but a straight line
+        // between the two is close enough.
+        let (self_weight, other_weight) = if dot > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = dot.acos();
+            let sin_angle = angle.sin();
+            (
+                ((1.0 - t) * angle).sin() / sin_angle,
+                (t * angle).sin() / sin_angle,
+            )
+        };
+        Self::new(
+            self.w * self_weight + other.w * other_weight,
+            self.x * self_weight + other.x * other_weight,
+            self.y * self_weight + other.y * other_weight,
+            self.z * self_weight + other.z * other_weight,
+        )
+        .normalize()
+    }
+}
+
+// Like matrices, the product rotates by other first, then by self.
+impl Mul for Quat {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        )
+    }
+}
+
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        // v + 2w(q x v) + 2q x (q x v), where q is the vector part. Cheaper than q * v * q^-1.
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = q.cross(v) * 2.0;
+        v + t * self.w + q.cross(t)
+    }
+}
+
+impl Mul<Vec4> for Quat {
+    type Output = Vec4;
+
+    fn mul(self, v: Vec4) -> Vec4 {
+        let rotated = self * v.to_vec3();
+        Vec4::new(rotated.x, rotated.y, rotated.z, v.w)
+    }
+}
+
+#[cfg(test)]
+fn assert_vec3_near(a: Vec3, b: Vec3) {
+    assert!(a.approx_eq(b, 1e-5), "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn test_quat_axis_angle() {
+    let q = Quat::from_axis_angle(Vec3::new(0.0, 2.0, 0.0), 90.0);
+    assert!((q.len() - 1.0).abs() < 1e-6);
+    let (axis, angle) = q.to_axis_angle();
+    assert_vec3_near(axis, Vec3::new(0.0, 1.0, 0.0));
+    assert!((angle - 90.0).abs() < 1e-4);
+
+    let (_, angle) = Quat::identity().to_axis_angle();
+    assert_eq!(angle, 0.0);
+
+    // Rotating a vector agrees with the matrix rotation.
+    let v = Vec3::new(1.0, 2.0, 3.0);
+    assert_vec3_near(q * v, Mat4::rotate_y(90.0) * v);
+    assert_vec3_near(q * v, Vec3::new(3.0, 2.0, -1.0));
+}
+
+#[test]
+fn test_quat_mat4() {
+    let axis = Vec3::new(1.0, -2.0, 0.5);
+    for angle in [0.0, 30.0, 120.0, 179.0, 180.0, 270.0] {
+        let q = Quat::from_axis_angle(axis, angle);
+        let m = q.to_mat4();
+        let expected = Mat4::rotate_axis(axis, angle);
+        for row in 0..4 {
+            for column in 0..4 {
+                assert!((m.get(row, column) - expected.get(row, column)).abs() < 1e-5);
+            }
+        }
+
+        // Back from the matrix, as q or -q (the same rotation).
+        let back = Quat::from_mat4(&m);
+        assert!((back.dot(q).abs() - 1.0).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn test_quat_mul() {
+    let a = Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), 30.0);
+    let b = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 45.0);
+    let v = Vec3::new(1.0, 2.0, 3.0);
+    assert_vec3_near((a * b) * v, a * (b * v));
+    assert_vec3_near((a * b) * v, Mat4::rotate_x(30.0) * Mat4::rotate_z(45.0) * v);
+    assert_vec3_near((a * a.conjugate()) * v, v);
+}
+
+#[test]
+fn test_quat_slerp() {
+    let a = Quat::identity();
+    let b = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 90.0);
+    assert_eq!(a.slerp(b, 0.0), a);
+    assert_vec3_near(
+        a.slerp(b, 1.0) * Vec3::new(1.0, 0.0, 0.0),
+        b * Vec3::new(1.0, 0.0, 0.0),
+    );
+
+    // Halfway is half the angle.
+    let (axis, angle) = a.slerp(b, 0.5).to_axis_angle();
+    assert_vec3_near(axis, Vec3::new(0.0, 0.0, 1.0));
+    assert!((angle - 45.0).abs() < 1e-3);
+
+    // -b is the same rotation as b, and slerp still takes the short way.
+    let minus_b = Quat::new(-b.w, -b.x, -b.y, -b.z);
+    let (_, angle) = a.slerp(minus_b, 0.5).to_axis_angle();
+    assert!((angle - 45.0).abs() < 1e-3);
+}
+
+#[test]
+fn test_quat_look_rotation() {
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let forward = Vec3::new(1.0, -1.0, 2.0);
+    let q = Quat::look_rotation(forward, up);
+    assert_vec3_near(q * Vec3::new(0.0, 0.0, 1.0), forward.unit_norm());
+    // The camera does not roll: its x axis stays level.
+    assert!((q * Vec3::new(1.0, 0.0, 0.0)).y.abs() < 1e-6);
+
+    // Looking straight up or down works too.
+    for forward in [up, -up] {
+        let q = Quat::look_rotation(forward, up);
+        assert_vec3_near(q * Vec3::new(0.0, 0.0, 1.0), forward);
+    }
+}
@@ -0,0 +1,411 @@
+// Shadow mapping. Before the camera view is drawn, the scene is drawn from each directional and
+// spot light into a depth buffer, the light's shadow map, with the same clipping and
+// rasterization as the camera view. A point is in shadow from a light when its shadow map has
+// something closer to the light than the point.
+use crate::camera::{Camera, Projection};
+use crate::framebuffer::RenderTarget;
+use crate::light::Light;
+use crate::mat::Mat4;
+use crate::texture::TextureFilter;
+use crate::tiles::TILE_SIZE;
+use crate::vec::Vec3;
+use crate::{
+    project_point_to_screen_space, push_mesh_draw_commands, DrawOptions, MeshPosition, Shading,
+    TriangleFill, View, World,
+};
+use rayon::prelude::*;
+use sdl2::pixels::Color;
+
+// The width and height of each shadow map, in texels.
+pub const SHADOW_MAP_SIZE: u32 = 1024;
+
+// A surface is only in shadow when it is further from the light than its shadow map by more than
+// the bias, so it does not shadow itself where the depth it drew into the shadow map was rounded.
+// The bias is in shadow map texels: SHADOW_BIAS, plus SHADOW_SLOPE_BIAS for each texel the depth
+// of the surface changes by across one texel, up to SHADOW_MAX_SLOPE.
+pub const SHADOW_BIAS: f32 = 1.0;
+pub const SHADOW_SLOPE_BIAS: f32 = 1.0;
+pub const SHADOW_MAX_SLOPE: f32 = 10.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Shadows {
+    None,
+    // A point is either in shadow or not, from the shadow map texel it falls in.
+    Hard,
+    // Percentage closer filtering: the fraction of the 3x3 texels around the point that are lit,
+    // which softens the edges of shadows.
+    Pcf,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShadowMap {
+    // The view from the light.
+    pub camera: Camera,
+    pub view_matrix: Mat4,
+    pub projection_matrix: Mat4,
+    pub size: u32,
+    // The distance along the light's view of the closest surface in each texel.
+    pub depth: Box<[f32]>,
+    pub pcf: bool,
+}
+
+impl ShadowMap {
+    pub fn new(camera: Camera, size: u32, pcf: bool) -> Self {
+        Self {
+            camera,
+            view_matrix: camera.view_matrix(),
+            projection_matrix: camera.projection_matrix(1.0),
+            size,
+            depth: vec![f32::INFINITY; (size * size) as usize].into_boxed_slice(),
+            pcf,
+        }
+    }
+
+    // The view from a light that sees all of the scene within the sphere at centre with radius.
+    // Directional lights look at it with orthographic projection, and spot lights with a field of
+    // view that covers their cone. Other lights cast no shadows.
+    pub fn light_camera(light: &Light, centre: Vec3, radius: f32) -> Option<Camera> {
+        match *light {
+            Light::Directional { direction, .. } => {
+                let location = centre - direction.unit_norm() * (radius * 2.0);
+                Some(Camera {
+                    projection: Projection::Orthographic,
+                    extent: radius * 2.0,
+                    z_near: radius,
+                    z_far: radius * 3.0,
+                    ..Camera::new(location, centre)
+                })
+            }
+            Light::Spot {
+                position,
+                direction,
+                outer_angle,
+                ..
+            } => Some(Camera {
+                fov: (outer_angle * 2.0).min(170.0),
+                z_far: position.distance(centre) + radius,
+                ..Camera::new(position, position + direction)
+            }),
+            Light::Ambient { .. } | Light::Point { .. } => None,
+        }
+    }
+
+    // Draw the depth of the meshes, as seen from the light. When tiled, bands of rows are drawn in
+    // parallel, with the same result.
+    pub fn render(&mut self, meshes: &[MeshPosition], tiled: bool) {
+        // Every face casts shadows, whichever way it faces the light. Faces are drawn with their
+        // textures, so the shadows of cut-outs have holes.
+        let options = DrawOptions {
+            draw_wireframe: false,
+            triangle_fill: TriangleFill::Texture,
+            shading: Shading::Flat,
+            backface_culling: false,
+            pause_rendering: true,
+            shuffle_faces: false,
+            tiled: false,
+            texture_filter: TextureFilter::Nearest,
+            shadows: Shadows::None,
+        };
+        let camera = self.camera;
+        let view = View {
+            camera: &camera,
+            options: &options,
+            lights: &[],
+            shadow_maps: &[],
+        };
+        let mut commands = Vec::new();
+        for mesh_position in meshes {
+            push_mesh_draw_commands(&mut commands, self.size, self.size, &view, mesh_position);
+        }
+        if !tiled {
+            for command in &commands {
+                command.draw(self);
+            }
+            return;
+        }
+        let size = self.size;
+        self.depth
+            .par_chunks_mut((TILE_SIZE * size) as usize)
+            .enumerate()
+            .for_each(|(band_index, depth)| {
+                let mut band = ShadowMapBand {
+                    y: band_index as u32 * TILE_SIZE,
+                    size,
+                    depth,
+                };
+                let (_, y_min, _, y_max) = band.bounds();
+                for command in &commands {
+                    let (_, command_y_min, _, command_y_max) = command.bounds();
+                    if command_y_min <= y_max && command_y_max >= y_min {
+                        command.draw(&mut band);
+                    }
+                }
+            });
+    }
+
+    // How much of the light reaches a world space position, from 0.0 in shadow to 1.0 lit.
+    // facing is the cosine of the angle between the surface normal and the direction to the
+    // light, which sets the slope bias.
+    pub fn visibility(&self, position: Vec3, facing: f32) -> f32 {
+        let p = self.view_matrix * position;
+        if p.z < self.camera.z_near || p.z > self.camera.z_far {
+            return 1.0;
+        }
+        let texel = project_point_to_screen_space(self.projection_matrix, self.size, self.size, p);
+        let (x, y) = (texel.x.floor() as i64, texel.y.floor() as i64);
+
+        // The size of a texel in world units, at the depth of the point.
+        let extent = match self.camera.projection {
+            Projection::Perspective => self.camera.extent_at_distance(p.z),
+            Projection::Orthographic => self.camera.extent,
+        };
+        let texel_size = extent / self.size as f32;
+        let slope = ((1.0 - facing * facing).max(0.0).sqrt() / facing).min(SHADOW_MAX_SLOPE);
+        // PCF compares against texels up to one away, where the surface is a texel's slope further.
+        let slope_texels = if self.pcf { slope * 2.0 } else { slope };
+        let depth = p.z - texel_size * (SHADOW_BIAS + SHADOW_SLOPE_BIAS * slope_texels);
+
+        let lit = |x: i64, y: i64| {
+            if x < 0 || y < 0 || x >= self.size as i64 || y >= self.size as i64 {
+                return true;
+            }
+            depth <= self.depth[(y * self.size as i64 + x) as usize]
+        };
+        if !self.pcf {
+            return if lit(x, y) { 1.0 } else { 0.0 };
+        }
+        let mut lit_count = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if lit(x + dx, y + dy) {
+                    lit_count += 1;
+                }
+            }
+        }
+        lit_count as f32 / 9.0
+    }
+}
+
+impl RenderTarget for ShadowMap {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        (0, 0, self.size as i32 - 1, self.size as i32 - 1)
+    }
+
+    // Only depth is kept.
+    fn set_pixel(&mut self, _x: u32, _y: u32, _color: Color) {}
+
+    fn set_pixel_z(&mut self, x: u32, y: u32, z: f32, _color: Color) {
+        if x >= self.size || y >= self.size {
+            return;
+        }
+        let i = (y * self.size + x) as usize;
+        if z < self.depth[i] {
+            self.depth[i] = z;
+        }
+    }
+
+    // Blended surfaces cast no shadows.
+    fn blend_pixel_z(&mut self, _x: u32, _y: u32, _z: f32, _color: Color) {}
+}
+
+// A band of TILE_SIZE rows of a shadow map, starting at row y, for drawing bands in parallel.
+struct ShadowMapBand<'a> {
+    y: u32,
+    size: u32,
+    depth: &'a mut [f32],
+}
+
+impl RenderTarget for ShadowMapBand<'_> {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        let rows = self.depth.len() as u32 / self.size;
+        (
+            0,
+            self.y as i32,
+            self.size as i32 - 1,
+            (self.y + rows) as i32 - 1,
+        )
+    }
+
+    fn set_pixel(&mut self, _x: u32, _y: u32, _color: Color) {}
+
+    fn set_pixel_z(&mut self, x: u32, y: u32, z: f32, _color: Color) {
+        if x >= self.size || y < self.y {
+            return;
+        }
+        let i = ((y - self.y) * self.size + x) as usize;
+        if i < self.depth.len() && z < self.depth[i] {
+            self.depth[i] = z;
+        }
+    }
+
+    fn blend_pixel_z(&mut self, _x: u32, _y: u32, _z: f32, _color: Color) {}
+}
+
+// The centre and radius of a sphere around all the meshes, in world space.
+pub fn scene_bounds(meshes: &[MeshPosition]) -> Option<(Vec3, f32)> {
+    let mut vertices = meshes.iter().flat_map(|mesh_position| {
+        let model_matrix = mesh_position.model_matrix();
+        mesh_position
+            .mesh
+            .vertices
+            .iter()
+            .map(move |&v| model_matrix * v)
+    });
+    let first = vertices.next()?;
+    let (min, max) = vertices.fold((first, first), |(min, max), v| (min.min(v), max.max(v)));
+    let centre = (min + max) / 2.0;
+    // A little bigger, so nothing is on the edge of the view, and never empty.
+    let radius = (min.distance(max) / 2.0 * 1.01).max(0.001);
+    Some((centre, radius))
+}
+
+// The shadow map of each light in the world that casts shadows, or nothing when shadows are off.
+pub fn render_shadow_maps(world: &World) -> Vec<Option<ShadowMap>> {
+    if world.options.shadows == Shadows::None {
+        return Vec::new();
+    }
+    let Some((centre, radius)) = scene_bounds(&world.meshes) else {
+        return Vec::new();
+    };
+    let pcf = world.options.shadows == Shadows::Pcf;
+    world
+        .lights
+        .iter()
+        .map(|light| {
+            let camera = ShadowMap::light_camera(light, centre, radius)?;
+            let mut shadow_map = ShadowMap::new(camera, SHADOW_MAP_SIZE, pcf);
+            shadow_map.render(&world.meshes, world.options.tiled);
+            Some(shadow_map)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn shadow_test_world(light: Light, shadows: Shadows) -> World {
+    use crate::mesh::Mesh;
+
+    // A cube floating above a wide, flat box, with its bottom at y = 0 and the floor at y = -0.9.
+    let cube = || Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap();
+    let mut floor = MeshPosition::new(cube(), Vec3::new(0.0, -1.0, 0.0));
+    floor.scale = Vec3::new(5.0, 0.1, 5.0);
+    World {
+        meshes: vec![MeshPosition::new(cube(), Vec3::new(0.0, 1.0, 0.0)), floor],
+        camera: Camera::new(Vec3::new(0.0, 3.0, -6.0), Vec3::new(0.0, 0.0, 0.0)),
+        options: DrawOptions {
+            draw_wireframe: false,
+            triangle_fill: TriangleFill::Color,
+            shading: Shading::Phong,
+            backface_culling: true,
+            pause_rendering: true,
+            shuffle_faces: false,
+            tiled: false,
+            texture_filter: TextureFilter::Nearest,
+            shadows,
+        },
+        lights: vec![
+            Light::Ambient {
+                color: Color::RGB(255, 255, 255),
+                intensity: 1.0,
+            },
+            light,
+        ],
+    }
+}
+
+#[test]
+fn test_directional_shadow_map() {
+    let sun = Light::Directional {
+        direction: Vec3::new(0.0, -1.0, 0.0),
+        color: Color::RGB(255, 255, 255),
+        intensity: 1.0,
+    };
+    let shadow_maps = render_shadow_maps(&shadow_test_world(sun, Shadows::Hard));
+    assert_eq!(shadow_maps.len(), 2);
+    assert!(shadow_maps[0].is_none());
+    let shadow_map = shadow_maps[1].as_ref().unwrap();
+
+    // Under the cube, next to it, and on the lit faces themselves.
+    let up = 1.0;
+    assert_eq!(shadow_map.visibility(Vec3::new(0.0, -0.9, 0.0), up), 0.0);
+    assert_eq!(shadow_map.visibility(Vec3::new(0.5, -0.9, -0.5), up), 0.0);
+    assert_eq!(shadow_map.visibility(Vec3::new(1.5, -0.9, 0.0), up), 1.0);
+    assert_eq!(shadow_map.visibility(Vec3::new(0.0, 2.0, 0.0), up), 1.0);
+    assert_eq!(shadow_map.visibility(Vec3::new(3.0, -0.9, 3.0), up), 1.0);
+
+    // Shadows are off.
+    let world = shadow_test_world(sun, Shadows::None);
+    assert!(render_shadow_maps(&world).is_empty());
+}
+
+#[test]
+fn test_shadow_map_tiled_matches_serial() {
+    let spot = Light::Spot {
+        position: Vec3::new(-4.0, 4.0, 0.0),
+        direction: Vec3::new(1.0, -1.0, 0.0),
+        inner_angle: 40.0,
+        outer_angle: 50.0,
+        color: Color::RGB(255, 255, 255),
+        intensity: 1.0,
+        attenuation: crate::light::Attenuation::NONE,
+    };
+    let mut world = shadow_test_world(spot, Shadows::Hard);
+    let serial = render_shadow_maps(&world).pop().unwrap().unwrap();
+    world.options.tiled = true;
+    let tiled = render_shadow_maps(&world).pop().unwrap().unwrap();
+    assert!(serial.depth == tiled.depth);
+    // Make sure there was something to compare.
+    assert!(serial.depth.iter().filter(|z| z.is_finite()).count() > 1000);
+}
+
+#[test]
+fn test_spot_shadow_map_pcf() {
+    // From above and to the left, so the shadow of the cube falls to the right.
+    let spot = Light::Spot {
+        position: Vec3::new(-4.0, 4.0, 0.0),
+        direction: Vec3::new(1.0, -1.0, 0.0),
+        inner_angle: 40.0,
+        outer_angle: 50.0,
+        color: Color::RGB(255, 255, 255),
+        intensity: 1.0,
+        attenuation: crate::light::Attenuation::NONE,
+    };
+    let facing = 45_f32.to_radians().cos();
+    let hard = render_shadow_maps(&shadow_test_world(spot, Shadows::Hard))
+        .pop()
+        .unwrap()
+        .unwrap();
+    let pcf = render_shadow_maps(&shadow_test_world(spot, Shadows::Pcf))
+        .pop()
+        .unwrap()
+        .unwrap();
+    assert_eq!(hard.camera.projection, Projection::Perspective);
+
+    for shadow_map in [&hard, &pcf] {
+        assert_eq!(
+            shadow_map.visibility(Vec3::new(2.0, -0.9, 0.0), facing),
+            0.0
+        );
+        assert_eq!(
+            shadow_map.visibility(Vec3::new(-2.0, -0.9, 0.0), facing),
+            1.0
+        );
+        // The lit top of the cube does not shadow itself.
+        assert_eq!(shadow_map.visibility(Vec3::new(0.0, 2.0, 0.0), facing), 1.0);
+    }
+
+    // Across the edge of the shadow, hard shadows switch from lit to shadowed from one texel to
+    // the next, and PCF fades over a few texels.
+    let across_edge = |shadow_map: &ShadowMap| {
+        (0..=400)
+            .map(|i| {
+                let z = i as f32 / 100.0;
+                shadow_map.visibility(Vec3::new(3.0, -0.9, z), facing)
+            })
+            .collect::<Vec<f32>>()
+    };
+    let hard_values = across_edge(&hard);
+    assert!(hard_values.iter().all(|&v| v == 0.0 || v == 1.0));
+    assert!(hard_values.contains(&0.0) && hard_values.contains(&1.0));
+    let pcf_values = across_edge(&pcf);
+    assert!(pcf_values.iter().any(|&v| v > 0.0 && v < 1.0));
+}
@@ -0,0 +1,312 @@
+// STL files describe a surface as a list of independent triangles, each with a facet normal.
+// There are no UVs, colors or shared vertices.
+use crate::material::Material;
+use crate::mesh::{
+    default_texture, generate_spherical_uvs, Encoding, Face, LineParser, Mesh, MeshLoadError,
+};
+use crate::vec::Vec3;
+use std::collections::HashMap;
+
+const HEADER_SIZE: usize = 80;
+// A normal and 3 vertices of 3 f32s, and a 2 byte attribute count.
+const TRIANGLE_SIZE: usize = 50;
+
+struct Facet {
+    normal: Vec3,
+    vertices: [Vec3; 3],
+}
+
+fn truncated(file_path: &str) -> MeshLoadError {
+    MeshLoadError::InvalidData {
+        path: file_path.to_string(),
+        message: String::from("unexpected end of file"),
+    }
+}
+
+/// Load an ASCII or binary STL file.
+/// Vertices at exactly the same position are merged, UVs are generated with a spherical
+/// projection and faces are lit with the facet normals from the file.
+pub fn load_stl(file_path: &str) -> Result<Mesh, MeshLoadError> {
+    let bytes = std::fs::read(file_path).map_err(|e| MeshLoadError::Io {
+        path: file_path.to_string(),
+        line: None,
+        source: e,
+    })?;
+    parse_stl(&bytes, file_path)
+}
+
+pub fn parse_stl(bytes: &[u8], file_path: &str) -> Result<Mesh, MeshLoadError> {
+    // ASCII files start with "solid", but so do the headers of some binary files.
+    // The size of a binary file follows from its triangle count, which tells them apart.
+    let binary_size = bytes.get(HEADER_SIZE..HEADER_SIZE + 4).map(|count| {
+        HEADER_SIZE + 4 + u32::from_le_bytes(count.try_into().unwrap()) as usize * TRIANGLE_SIZE
+    });
+    let facets =
+        if bytes.trim_ascii_start().starts_with(b"solid") && binary_size != Some(bytes.len()) {
+            parse_ascii(bytes, file_path)?
+        } else {
+            parse_binary(bytes, file_path)?
+        };
+    Ok(build_mesh(&facets))
+}
+
+fn parse_binary(bytes: &[u8], file_path: &str) -> Result<Vec<Facet>, MeshLoadError> {
+    let count_bytes = bytes
+        .get(HEADER_SIZE..HEADER_SIZE + 4)
+        .ok_or_else(|| truncated(file_path))?;
+    let count = u32::from_le_bytes(count_bytes.try_into().unwrap()) as usize;
+    let data = &bytes[HEADER_SIZE + 4..];
+    if data.len() < count * TRIANGLE_SIZE {
+        return Err(truncated(file_path));
+    }
+
+    let read_vec3 = |b: &[u8]| {
+        let f = |i: usize| f32::from_le_bytes(b[i * 4..i * 4 + 4].try_into().unwrap());
+        Vec3::new(f(0), f(1), f(2))
+    };
+    Ok(data
+        .chunks_exact(TRIANGLE_SIZE)
+        .take(count)
+        .map(|triangle| Facet {
+            normal: read_vec3(&triangle[0..12]),
+            vertices: [
+                read_vec3(&triangle[12..24]),
+                read_vec3(&triangle[24..36]),
+                read_vec3(&triangle[36..48]),
+            ],
+        })
+        .collect())
+}
+
+fn parse_ascii(bytes: &[u8], file_path: &str) -> Result<Vec<Facet>, MeshLoadError> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut facets: Vec<Facet> = Vec::new();
+    // The facet being read, with its vertices so far.
+    let mut facet: Option<(Vec3, Vec<Vec3>)> = None;
+
+    for (line_index, line) in text.lines().enumerate() {
+        let parser = LineParser {
+            path: file_path,
+            line: line_index + 1,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let parse_vec3 = |words: &[&str]| -> Result<Vec3, MeshLoadError> {
+            if words.len() != 3 {
+                return Err(parser.malformed_line(line));
+            }
+            Ok(Vec3::new(
+                parser.parse_f32(words[0])?,
+                parser.parse_f32(words[1])?,
+                parser.parse_f32(words[2])?,
+            ))
+        };
+        match words.first() {
+            Some(&"facet") => {
+                if facet.is_some() || words.get(1) != Some(&"normal") {
+                    return Err(parser.malformed_line(line));
+                }
+                facet = Some((parse_vec3(&words[2..])?, Vec::with_capacity(3)));
+            }
+            Some(&"vertex") => match facet.as_mut() {
+                Some((_, vertices)) if vertices.len() < 3 => {
+                    vertices.push(parse_vec3(&words[1..])?)
+                }
+                _ => return Err(parser.malformed_line(line)),
+            },
+            Some(&"endfacet") => match facet.take() {
+                Some((normal, vertices)) if vertices.len() == 3 => facets.push(Facet {
+                    normal,
+                    vertices: [vertices[0], vertices[1], vertices[2]],
+                }),
+                _ => return Err(parser.malformed_line(line)),
+            },
+            // solid, outer loop, endloop and endsolid carry no data.
+            _ => {}
+        }
+    }
+    if facet.is_some() {
+        return Err(truncated(file_path));
+    }
+    Ok(facets)
+}
+
+fn build_mesh(facets: &[Facet]) -> Mesh {
+    let mut vertices: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::with_capacity(facets.len());
+    let mut faces: Vec<Face> = Vec::with_capacity(facets.len());
+    let mut vertex_indices: HashMap<[u32; 3], usize> = HashMap::new();
+
+    for facet in facets {
+        let [a, b, c] = facet.vertices.map(|v| {
+            // Adding 0.0 turns -0.0 into 0.0, so both merge into the same vertex.
+            let key = [v.x, v.y, v.z].map(|f| (f + 0.0).to_bits());
+            *vertex_indices.entry(key).or_insert_with(|| {
+                vertices.push(v);
+                vertices.len() - 1
+            })
+        });
+        // Many exporters write a zero normal and leave it to the reader to compute it.
+        let normal = if facet.normal.len() > 0.0 {
+            facet.normal.unit_norm()
+        } else {
+            face_normal(facet.vertices[0], facet.vertices[1], facet.vertices[2])
+        };
+        let n = normals.len();
+        normals.push(normal);
+        faces.push(Face {
+            a_normal: n,
+            b_normal: n,
+            c_normal: n,
+            ..Face::new(a, b, c, a, b, c)
+        });
+    }
+
+    Mesh {
+        uvs: generate_spherical_uvs(&vertices),
+        vertices,
+        normals,
+        faces,
+        materials: vec![Material::default()],
+        texture: default_texture(),
+        colors: Vec::new(),
+    }
+}
+
+fn face_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    let normal = (b - a).cross(c - a);
+    if normal.len() > 0.0 {
+        normal.unit_norm()
+    } else {
+        normal
+    }
+}
+
+/// Write the faces of the mesh as STL. Facet normals are computed from the vertices,
+/// and UVs, normals, colors and materials are not written.
+pub fn write_stl<W: std::io::Write>(
+    mesh: &Mesh,
+    writer: &mut W,
+    encoding: Encoding,
+) -> std::io::Result<()> {
+    let facets = mesh.faces.iter().map(|face| {
+        let (a, b, c) = (
+            mesh.vertices[face.a],
+            mesh.vertices[face.b],
+            mesh.vertices[face.c],
+        );
+        (face_normal(a, b, c), [a, b, c])
+    });
+
+    match encoding {
+        Encoding::Ascii => {
+            writeln!(writer, "solid mesh")?;
+            for (n, vertices) in facets {
+                writeln!(writer, "  facet normal {} {} {}", n.x, n.y, n.z)?;
+                writeln!(writer, "    outer loop")?;
+                for v in vertices {
+                    writeln!(writer, "      vertex {} {} {}", v.x, v.y, v.z)?;
+                }
+                writeln!(writer, "    endloop")?;
+                writeln!(writer, "  endfacet")?;
+            }
+            writeln!(writer, "endsolid mesh")
+        }
+        Encoding::Binary => {
+            // The header must not start with "solid", or readers may take the file for ASCII.
+            let mut header = [b' '; HEADER_SIZE];
+            let title = b"binary STL written by graphics_from_scratch";
+            header[..title.len()].copy_from_slice(title);
+            writer.write_all(&header)?;
+            writer.write_all(&(mesh.faces.len() as u32).to_le_bytes())?;
+            for (n, vertices) in facets {
+                for v in std::iter::once(n).chain(vertices) {
+                    for f in [v.x, v.y, v.z] {
+                        writer.write_all(&f.to_le_bytes())?;
+                    }
+                }
+                writer.write_all(&0u16.to_le_bytes())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_stl_round_trip() {
+    let cube = Mesh::load_mesh("./assets/cube.obj", "./assets/cube.png").unwrap();
+
+    for encoding in [Encoding::Ascii, Encoding::Binary] {
+        let mut bytes: Vec<u8> = Vec::new();
+        write_stl(&cube, &mut bytes, encoding).unwrap();
+        let mesh = parse_stl(&bytes, "test.stl").unwrap();
+
+        // The corners of the cube are shared by several faces, and merged again when loading.
+        assert_eq!(mesh.vertices.len(), 8);
+        assert_eq!(mesh.uvs.len(), 8);
+        assert_eq!(mesh.faces.len(), cube.faces.len());
+        for (face, cube_face) in mesh.faces.iter().zip(cube.faces.iter()) {
+            assert_eq!(mesh.vertices[face.a], cube.vertices[cube_face.a]);
+            assert_eq!(mesh.vertices[face.b], cube.vertices[cube_face.b]);
+            assert_eq!(mesh.vertices[face.c], cube.vertices[cube_face.c]);
+            let (a, b, c) = (
+                cube.vertices[cube_face.a],
+                cube.vertices[cube_face.b],
+                cube.vertices[cube_face.c],
+            );
+            assert_eq!(mesh.normals[face.a_normal], face_normal(a, b, c));
+        }
+    }
+}
+
+#[test]
+fn test_parse_stl_ascii() {
+    // A zero normal is computed from the vertices.
+    let stl = "solid triangle\n\
+               facet normal 0 0 0\n\
+               outer loop\n\
+               vertex 0 0 0\n\
+               vertex 1 0 0\n\
+               vertex 0 1 0\n\
+               endloop\n\
+               endfacet\n\
+               endsolid triangle\n";
+    let mesh = parse_stl(stl.as_bytes(), "test.stl").unwrap();
+    assert_eq!(mesh.vertices.len(), 3);
+    assert_eq!(
+        mesh.faces,
+        vec![Face {
+            a_normal: 0,
+            b_normal: 0,
+            c_normal: 0,
+            ..Face::new(0, 1, 2, 0, 1, 2)
+        }]
+    );
+    assert_eq!(mesh.normals, vec![Vec3::new(0.0, 0.0, 1.0)]);
+    assert!(mesh.colors.is_empty());
+}
+
+#[test]
+fn test_parse_stl_errors() {
+    match parse_stl(b"solid a\nfacet normal 0 0 1\nvertex 0 x 0\n", "test.stl") {
+        Err(MeshLoadError::ParseNumber { path, line, text }) => {
+            assert_eq!(path, "test.stl");
+            assert_eq!(line, 3);
+            assert_eq!(text, "x");
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.faces)),
+    }
+
+    match parse_stl(b"solid a\nvertex 0 0 0\n", "test.stl") {
+        Err(MeshLoadError::MalformedLine { line, .. }) => assert_eq!(line, 2),
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.faces)),
+    }
+
+    // A binary file that claims 2 triangles but holds only 1.
+    let mut bytes = vec![0u8; HEADER_SIZE];
+    bytes.extend_from_slice(&2u32.to_le_bytes());
+    bytes.extend_from_slice(&[0u8; TRIANGLE_SIZE]);
+    match parse_stl(&bytes, "test.stl") {
+        Err(MeshLoadError::InvalidData { path, .. }) => assert_eq!(path, "test.stl"),
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.faces)),
+    }
+}
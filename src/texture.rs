@@ -0,0 +1,406 @@
+// Textures and texture sampling.
+//
+// A texture keeps a chain of mip levels, each half the size of the one before it down to 1x1,
+// so distant surfaces can be sampled from a level that has about one texel per pixel instead of
+// skipping over texels of the full size image.
+//
+// Images have straight alpha, but filtering and downsampling weight the colors by their alpha
+// (premultiplied alpha), so the color of transparent texels does not bleed into their neighbours.
+use crate::vec::{Vec2, Vec4};
+use image::{ImageBuffer, Rgba};
+use sdl2::pixels::Color;
+
+pub type RgbaImage = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TextureFilter {
+    // The closest texel of the full size image.
+    Nearest,
+    // A blend of the four closest texels of the full size image.
+    Bilinear,
+    // A blend of bilinear samples of the two mip levels closest to the level of detail.
+    Trilinear,
+}
+
+// What is sampled for UV coordinates outside 0 to 1.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TextureWrap {
+    // The texture repeats, so 1.25 samples the same as 0.25.
+    Repeat,
+    // The texture repeats, flipped every other time, so 1.25 samples the same as 0.75.
+    MirroredRepeat,
+    // The texels at the edge of the texture are stretched outwards.
+    ClampToEdge,
+    // Everything outside the texture is the border color.
+    ClampToBorder(Color),
+}
+
+impl TextureWrap {
+    // The texel to sample for a texel index, in a row or column of size texels, or the border
+    // color to sample instead.
+    fn texel_index(self, i: i64, size: u32) -> Result<u32, Color> {
+        let size = size as i64;
+        let i = match self {
+            TextureWrap::Repeat => i.rem_euclid(size),
+            TextureWrap::MirroredRepeat => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+            TextureWrap::ClampToEdge => i.clamp(0, size - 1),
+            TextureWrap::ClampToBorder(color) => {
+                if i < 0 || i >= size {
+                    return Err(color);
+                }
+                i
+            }
+        };
+        Ok(i as u32)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Texture {
+    // The full size image first, then each level half the size of the previous one.
+    pub levels: Vec<RgbaImage>,
+    // How U and V coordinates outside 0 to 1 are sampled.
+    pub wrap_u: TextureWrap,
+    pub wrap_v: TextureWrap,
+}
+
+impl Texture {
+    // A texture that clamps to the edge in both directions.
+    pub fn new(image: RgbaImage) -> Self {
+        let mut levels = vec![image];
+        loop {
+            let previous = &levels[levels.len() - 1];
+            if previous.width() == 1 && previous.height() == 1 {
+                break;
+            }
+            let next = downsample(previous);
+            levels.push(next);
+        }
+        Texture {
+            levels,
+            wrap_u: TextureWrap::ClampToEdge,
+            wrap_v: TextureWrap::ClampToEdge,
+        }
+    }
+
+    pub fn with_wrap(mut self, wrap_u: TextureWrap, wrap_v: TextureWrap) -> Self {
+        self.wrap_u = wrap_u;
+        self.wrap_v = wrap_v;
+        self
+    }
+
+    // The full size image.
+    pub fn image(&self) -> &RgbaImage {
+        &self.levels[0]
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image().width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image().height()
+    }
+
+    // The mip level to sample, from how far the UV coordinates move from one pixel to the next
+    // horizontally and vertically. Level 0 when a texel covers a pixel or more, and 1 more for
+    // every doubling of the texels per pixel.
+    pub fn level_of_detail(&self, uv_step_x: Vec2, uv_step_y: Vec2) -> f32 {
+        let size = Vec2::new(self.width() as f32, self.height() as f32);
+        let texels_per_pixel = (uv_step_x * size).len().max((uv_step_y * size).len());
+        if !texels_per_pixel.is_finite() || texels_per_pixel <= 1.0 {
+            return 0.0;
+        }
+        texels_per_pixel.log2().min((self.levels.len() - 1) as f32)
+    }
+
+    pub fn sample(&self, uv: Vec2, level_of_detail: f32, filter: TextureFilter) -> Color {
+        let premultiplied = match filter {
+            TextureFilter::Nearest => {
+                let (x, y) = texel_position(self.image(), uv);
+                // Nothing is filtered, so the texel is returned as it is.
+                let color = self.texel(0, x.floor() as i64, y.floor() as i64);
+                return Color::RGBA(color.x as u8, color.y as u8, color.z as u8, color.w as u8);
+            }
+            TextureFilter::Bilinear => self.sample_bilinear(0, uv),
+            TextureFilter::Trilinear => {
+                let lod = level_of_detail.clamp(0.0, (self.levels.len() - 1) as f32);
+                let level = lod.floor() as usize;
+                let fine = self.sample_bilinear(level, uv);
+                if level + 1 < self.levels.len() {
+                    let coarse = self.sample_bilinear(level + 1, uv);
+                    fine.lerp(coarse, lod - level as f32)
+                } else {
+                    fine
+                }
+            }
+        };
+        let [r, g, b, a] = unpremultiply(premultiplied);
+        Color::RGBA(r, g, b, a)
+    }
+
+    // A texel of a mip level, wrapped into the level, with straight alpha.
+    fn texel(&self, level: usize, x: i64, y: i64) -> Vec4 {
+        let image = &self.levels[level];
+        let x = self.wrap_u.texel_index(x, image.width());
+        let y = self.wrap_v.texel_index(y, image.height());
+        match (x, y) {
+            (Ok(x), Ok(y)) => rgba_to_vec4(image.get_pixel(x, y)),
+            (Err(border), _) | (_, Err(border)) => Vec4::new(
+                border.r as f32,
+                border.g as f32,
+                border.b as f32,
+                border.a as f32,
+            ),
+        }
+    }
+
+    // A bilinear sample of a mip level, with premultiplied alpha.
+    fn sample_bilinear(&self, level: usize, uv: Vec2) -> Vec4 {
+        let texel = |x: i64, y: i64| premultiply(self.texel(level, x, y));
+        let (x, y) = texel_position(&self.levels[level], uv);
+        if !x.is_finite() || !y.is_finite() {
+            return texel(0, 0);
+        }
+        // The texel centres around the position.
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = texel(x0, y0).lerp(texel(x0 + 1, y0), tx);
+        let bottom = texel(x0, y0 + 1).lerp(texel(x0 + 1, y0 + 1), tx);
+        top.lerp(bottom, ty)
+    }
+}
+
+impl From<RgbaImage> for Texture {
+    fn from(image: RgbaImage) -> Self {
+        Texture::new(image)
+    }
+}
+
+// The position of a UV coordinate in texels, from the top left corner of the image. UV 0, 0 is
+// the bottom left corner of the image and 1, 1 the top right corner, so the texture repeats
+// without a seam.
+fn texel_position(image: &RgbaImage, uv: Vec2) -> (f32, f32) {
+    (
+        image.width() as f32 * uv.x,
+        image.height() as f32 * (1.0 - uv.y),
+    )
+}
+
+fn rgba_to_vec4(texel: &Rgba<u8>) -> Vec4 {
+    let [r, g, b, a] = texel.0;
+    Vec4::new(r as f32, g as f32, b as f32, a as f32)
+}
+
+// Straight alpha color channels multiplied by alpha, all from 0.0 to 255.0.
+fn premultiply(color: Vec4) -> Vec4 {
+    let alpha = color.w / 255.0;
+    Vec4::new(color.x * alpha, color.y * alpha, color.z * alpha, color.w)
+}
+
+// Back to straight alpha. Fully transparent colors are black.
+fn unpremultiply(color: Vec4) -> [u8; 4] {
+    let alpha = color.w.round().clamp(0.0, 255.0);
+    if alpha == 0.0 {
+        return [0, 0, 0, 0];
+    }
+    let channel = |c: f32| (c * 255.0 / color.w).round().clamp(0.0, 255.0) as u8;
+    [
+        channel(color.x),
+        channel(color.y),
+        channel(color.z),
+        alpha as u8,
+    ]
+}
+
+fn clamped_texel(image: &RgbaImage, x: i64, y: i64) -> Vec4 {
+    let x = x.clamp(0, image.width() as i64 - 1) as u32;
+    let y = y.clamp(0, image.height() as i64 - 1) as u32;
+    premultiply(rgba_to_vec4(image.get_pixel(x, y)))
+}
+
+// The next mip level: half the size, rounded down but at least 1, with each texel the average of
+// the 2x2 texels it covers, weighted by their alpha.
+fn downsample(image: &RgbaImage) -> RgbaImage {
+    let width = (image.width() / 2).max(1);
+    let height = (image.height() / 2).max(1);
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64 * 2, y as i64 * 2);
+        let sum = clamped_texel(image, x, y)
+            + clamped_texel(image, x + 1, y)
+            + clamped_texel(image, x, y + 1)
+            + clamped_texel(image, x + 1, y + 1);
+        Rgba(unpremultiply(sum / 4.0))
+    })
+}
+
+#[cfg(test)]
+fn grey_texel(value: u8) -> Rgba<u8> {
+    Rgba([value, value, value, 255])
+}
+
+#[test]
+fn test_texture_mip_chain() {
+    let texture = Texture::new(ImageBuffer::from_pixel(12, 5, Rgba([10, 20, 30, 255])));
+    let sizes: Vec<(u32, u32)> = texture
+        .levels
+        .iter()
+        .map(|level| (level.width(), level.height()))
+        .collect();
+    assert_eq!(sizes, vec![(12, 5), (6, 2), (3, 1), (1, 1)]);
+    for level in &texture.levels {
+        assert!(level.pixels().all(|&p| p == Rgba([10, 20, 30, 255])));
+    }
+
+    // A checkerboard averages to grey.
+    let checkerboard = ImageBuffer::from_fn(4, 4, |x, y| {
+        if (x + y) % 2 == 0 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    let texture = Texture::new(checkerboard);
+    assert_eq!(texture.levels.len(), 3);
+    assert_eq!(
+        *texture.levels[2].get_pixel(0, 0),
+        Rgba([128, 128, 128, 255])
+    );
+}
+
+#[test]
+fn test_texture_sample_filters() {
+    // Black on the left, grey on the right.
+    let image = ImageBuffer::from_fn(2, 2, |x, _| grey_texel(x as u8 * 200));
+    let texture = Texture::new(image);
+    let uv = Vec2::new(0.375, 0.5);
+    assert_eq!(
+        texture.sample(uv, 0.0, TextureFilter::Nearest),
+        Color::RGB(0, 0, 0)
+    );
+    assert_eq!(
+        texture.sample(uv, 0.0, TextureFilter::Bilinear),
+        Color::RGB(50, 50, 50)
+    );
+    // Level 1 is the 1x1 average.
+    assert_eq!(
+        texture.sample(uv, 1.0, TextureFilter::Trilinear),
+        Color::RGB(100, 100, 100)
+    );
+    assert_eq!(
+        texture.sample(uv, 0.5, TextureFilter::Trilinear),
+        Color::RGB(75, 75, 75)
+    );
+}
+
+#[test]
+fn test_texture_level_of_detail() {
+    let texture = Texture::new(ImageBuffer::new(64, 32));
+    // One texel per pixel, and magnified.
+    assert_eq!(
+        texture.level_of_detail(Vec2::new(1.0 / 64.0, 0.0), Vec2::new(0.0, 1.0 / 32.0)),
+        0.0
+    );
+    assert_eq!(
+        texture.level_of_detail(Vec2::new(0.001, 0.0), Vec2::new(0.0, 0.001)),
+        0.0
+    );
+    // Four texels per pixel vertically.
+    assert_eq!(
+        texture.level_of_detail(Vec2::new(1.0 / 64.0, 0.0), Vec2::new(0.0, 4.0 / 32.0)),
+        2.0
+    );
+    // Never past the last level.
+    assert_eq!(
+        texture.level_of_detail(Vec2::new(100.0, 0.0), Vec2::new(0.0, 100.0)),
+        6.0
+    );
+}
+
+#[test]
+fn test_texture_wrap() {
+    // A row of four texels, 0, 10, 20 and 30.
+    let row = Texture::new(ImageBuffer::from_fn(4, 1, |x, _| grey_texel(x as u8 * 10)));
+    let border = Color::RGB(255, 0, 0);
+    let sample = |wrap: TextureWrap, u: f32, filter: TextureFilter| {
+        let texture = row.clone().with_wrap(wrap, TextureWrap::ClampToEdge);
+        texture.sample(Vec2::new(u, 0.5), 0.0, filter)
+    };
+    let grey = |value: u8| Color::RGB(value, value, value);
+
+    for (wrap, past_end, before_start) in [
+        (TextureWrap::Repeat, grey(0), grey(30)),
+        (TextureWrap::MirroredRepeat, grey(30), grey(0)),
+        (TextureWrap::ClampToEdge, grey(30), grey(0)),
+        (TextureWrap::ClampToBorder(border), border, border),
+    ] {
+        assert_eq!(sample(wrap, 0.375, TextureFilter::Nearest), grey(10));
+        assert_eq!(sample(wrap, 1.125, TextureFilter::Nearest), past_end);
+        assert_eq!(sample(wrap, -0.125, TextureFilter::Nearest), before_start);
+    }
+
+    // Bilinear filtering blends across the edge into the wrapped texel.
+    assert_eq!(
+        sample(TextureWrap::Repeat, 0.0, TextureFilter::Bilinear),
+        grey(15)
+    );
+    assert_eq!(
+        sample(TextureWrap::ClampToEdge, 0.0, TextureFilter::Bilinear),
+        grey(0)
+    );
+
+    // U and V wrap independently. The top row is 100 and the bottom row 200.
+    let column = Texture::new(ImageBuffer::from_fn(1, 2, |_, y| {
+        grey_texel(100 + y as u8 * 100)
+    }))
+    .with_wrap(TextureWrap::ClampToBorder(border), TextureWrap::Repeat);
+    let nearest = |u: f32, v: f32| column.sample(Vec2::new(u, v), 0.0, TextureFilter::Nearest);
+    assert_eq!(nearest(0.5, 1.25), grey(200));
+    assert_eq!(nearest(0.5, -0.25), grey(100));
+    assert_eq!(nearest(1.5, 0.25), border);
+}
+
+#[test]
+fn test_texture_alpha() {
+    // Opaque red on the left, fully transparent on the right.
+    let image = ImageBuffer::from_fn(2, 1, |x, _| {
+        if x == 0 {
+            Rgba([255, 0, 0, 255])
+        } else {
+            Rgba([0, 0, 0, 0])
+        }
+    });
+    let texture = Texture::new(image);
+    let uv = Vec2::new(0.375, 0.5);
+    assert_eq!(
+        texture.sample(uv, 0.0, TextureFilter::Nearest),
+        Color::RGBA(255, 0, 0, 255)
+    );
+    // Alpha is filtered like the other channels, and the black of the transparent texel does not
+    // darken the red.
+    assert_eq!(
+        texture.sample(uv, 0.0, TextureFilter::Bilinear),
+        Color::RGBA(255, 0, 0, 191)
+    );
+    assert_eq!(*texture.levels[1].get_pixel(0, 0), Rgba([255, 0, 0, 128]));
+    assert_eq!(
+        texture.sample(uv, 1.0, TextureFilter::Trilinear),
+        Color::RGBA(255, 0, 0, 128)
+    );
+
+    let border = TextureWrap::ClampToBorder(Color::RGBA(0, 0, 255, 0));
+    let texture = texture.with_wrap(border, border);
+    assert_eq!(
+        texture.sample(Vec2::new(1.5, 0.5), 0.0, TextureFilter::Nearest),
+        Color::RGBA(0, 0, 255, 0)
+    );
+}
@@ -0,0 +1,251 @@
+// Multithreaded rasterization. The framebuffer is split into square tiles, and every draw command
+// is binned into the tiles its bounds overlap. Each tile then runs its commands, in the original
+// order, on a thread of its own, into a copy of its part of the framebuffer.
+//
+// Every pixel sees the same commands in the same order as when drawing on one thread, and the
+// color and depth of a pixel only depend on the command and the pixel, so the image is exactly
+// the same.
+use crate::framebuffer::{blend_over, Framebuffer, RenderTarget, SIZE_OF_COLOR};
+use crate::DrawCommand;
+use rayon::prelude::*;
+use sdl2::pixels::{Color, PixelFormat};
+
+pub const TILE_SIZE: u32 = 64;
+
+// PixelFormat wraps a pointer to SDL's description of the format, so it is neither Send nor Sync.
+// SDL never changes a format after creating it, and converting colors only reads it, so the tile
+// threads can share it.
+struct SharedPixelFormat<'a>(&'a PixelFormat);
+
+unsafe impl Sync for SharedPixelFormat<'_> {}
+
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    color_buffer: Vec<u8>,
+    z_buffer: Vec<f32>,
+}
+
+impl Tile {
+    // A copy of the part of the framebuffer from x, y to x + width, y + height.
+    fn copy_from(
+        color_buffer: &[u8],
+        z_buffer: &[f32],
+        framebuffer_width: u32,
+        (x, y, width, height): (u32, u32, u32, u32),
+    ) -> Self {
+        let mut tile = Self {
+            x,
+            y,
+            width,
+            height,
+            color_buffer: Vec::with_capacity((width * height) as usize * SIZE_OF_COLOR),
+            z_buffer: Vec::with_capacity((width * height) as usize),
+        };
+        for row in y..y + height {
+            let start = (row * framebuffer_width + x) as usize;
+            let end = start + width as usize;
+            tile.color_buffer
+                .extend_from_slice(&color_buffer[start * SIZE_OF_COLOR..end * SIZE_OF_COLOR]);
+            tile.z_buffer.extend_from_slice(&z_buffer[start..end]);
+        }
+        tile
+    }
+
+    fn copy_to(&self, framebuffer: &mut Framebuffer) {
+        for tile_row in 0..self.height {
+            let start = ((self.y + tile_row) * framebuffer.width + self.x) as usize;
+            let end = start + self.width as usize;
+            let tile_start = (tile_row * self.width) as usize;
+            let tile_end = tile_start + self.width as usize;
+            framebuffer.color_buffer[start * SIZE_OF_COLOR..end * SIZE_OF_COLOR].copy_from_slice(
+                &self.color_buffer[tile_start * SIZE_OF_COLOR..tile_end * SIZE_OF_COLOR],
+            );
+            framebuffer.z_buffer[start..end].copy_from_slice(&self.z_buffer[tile_start..tile_end]);
+        }
+    }
+
+    // The index of a pixel in the tile, from framebuffer coordinates.
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.x || y < self.y || x >= self.x + self.width || y >= self.y + self.height {
+            return None;
+        }
+        Some(((y - self.y) * self.width + (x - self.x)) as usize)
+    }
+}
+
+// A tile being drawn into. The tile only borrows the pixel format while drawing, so the framebuffer
+// is free again when the tiles are copied back.
+struct TileTarget<'a> {
+    tile: &'a mut Tile,
+    pixel_format: &'a SharedPixelFormat<'a>,
+}
+
+impl TileTarget<'_> {
+    fn write_color(&mut self, i: usize, color: Color) {
+        let i_color = i * SIZE_OF_COLOR;
+        let color_bytes: &[u8; SIZE_OF_COLOR] = &color.to_u32(self.pixel_format.0).to_ne_bytes();
+        self.tile.color_buffer[i_color..i_color + SIZE_OF_COLOR].copy_from_slice(color_bytes);
+    }
+
+    fn read_color(&self, i: usize) -> Color {
+        let i_color = i * SIZE_OF_COLOR;
+        let mut color_bytes = [0u8; SIZE_OF_COLOR];
+        color_bytes.copy_from_slice(&self.tile.color_buffer[i_color..i_color + SIZE_OF_COLOR]);
+        Color::from_u32(self.pixel_format.0, u32::from_ne_bytes(color_bytes))
+    }
+}
+
+impl RenderTarget for TileTarget<'_> {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        let tile = &self.tile;
+        (
+            tile.x as i32,
+            tile.y as i32,
+            (tile.x + tile.width) as i32 - 1,
+            (tile.y + tile.height) as i32 - 1,
+        )
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        if let Some(i) = self.tile.index(x, y) {
+            self.write_color(i, color);
+        }
+    }
+
+    fn set_pixel_z(&mut self, x: u32, y: u32, z: f32, color: Color) {
+        if let Some(i) = self.tile.index(x, y) {
+            if z < self.tile.z_buffer[i] {
+                self.tile.z_buffer[i] = z;
+                self.write_color(i, color);
+            }
+        }
+    }
+
+    fn blend_pixel_z(&mut self, x: u32, y: u32, z: f32, color: Color) {
+        if let Some(i) = self.tile.index(x, y) {
+            if z < self.tile.z_buffer[i] {
+                let below = self.read_color(i);
+                self.write_color(i, blend_over(color, below));
+            }
+        }
+    }
+}
+
+/// Draw the commands into the framebuffer, rasterizing tiles in parallel.
+/// The result is the same as drawing the commands in order on one thread.
+pub fn draw_tiled(framebuffer: &mut Framebuffer, commands: &[DrawCommand]) {
+    let tiles_x = framebuffer.width.div_ceil(TILE_SIZE);
+    let tiles_y = framebuffer.height.div_ceil(TILE_SIZE);
+
+    // The commands that touch each tile, in order.
+    let mut bins: Vec<Vec<usize>> = vec![Vec::new(); (tiles_x * tiles_y) as usize];
+    let (_, _, screen_x_max, screen_y_max) = framebuffer.bounds();
+    for (i, command) in commands.iter().enumerate() {
+        let (x_min, y_min, x_max, y_max) = command.bounds();
+        let (x_min, y_min) = (x_min.max(0), y_min.max(0));
+        let (x_max, y_max) = (x_max.min(screen_x_max), y_max.min(screen_y_max));
+        if x_min > x_max || y_min > y_max {
+            continue;
+        }
+        for tile_y in y_min as u32 / TILE_SIZE..=y_max as u32 / TILE_SIZE {
+            for tile_x in x_min as u32 / TILE_SIZE..=x_max as u32 / TILE_SIZE {
+                bins[(tile_y * tiles_x + tile_x) as usize].push(i);
+            }
+        }
+    }
+
+    let pixel_format = SharedPixelFormat(&framebuffer.pixel_format);
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    let (color_buffer, z_buffer) = (&framebuffer.color_buffer, &framebuffer.z_buffer);
+    let tiles: Vec<Tile> = bins
+        .par_iter()
+        .enumerate()
+        .filter(|(_, bin)| !bin.is_empty())
+        .map(|(tile_index, bin)| {
+            let x = (tile_index as u32 % tiles_x) * TILE_SIZE;
+            let y = (tile_index as u32 / tiles_x) * TILE_SIZE;
+            let size = (TILE_SIZE.min(width - x), TILE_SIZE.min(height - y));
+            let mut tile = Tile::copy_from(color_buffer, z_buffer, width, (x, y, size.0, size.1));
+            let mut target = TileTarget {
+                tile: &mut tile,
+                pixel_format: &pixel_format,
+            };
+            for &i in bin {
+                commands[i].draw(&mut target);
+            }
+            tile
+        })
+        .collect();
+
+    for tile in tiles {
+        tile.copy_to(framebuffer);
+    }
+}
+
+#[test]
+fn test_draw_tiled_matches_serial() {
+    use crate::camera::Camera;
+    use crate::light::default_lights;
+    use crate::mesh::Mesh;
+    use crate::shadow::Shadows;
+    use crate::texture::TextureFilter;
+    use crate::vec::Vec3;
+    use crate::{draw_meshes, DrawOptions, MeshPosition, Shading, TriangleFill, World};
+    use sdl2::pixels::PixelFormatEnum;
+
+    let mut world = World {
+        meshes: vec![
+            MeshPosition::new(
+                Mesh::load_mesh("./assets/crab.obj", "./assets/crab.png").unwrap(),
+                Vec3::new(-1.0, 0.0, 0.0),
+            ),
+            MeshPosition::new(
+                Mesh::load_mesh("./assets/drone.obj", "./assets/drone.png").unwrap(),
+                Vec3::new(1.0, 0.5, 0.5),
+            ),
+        ],
+        camera: Camera::new(Vec3::new(0.5, 1.0, -3.0), Vec3::new(0.0, 0.0, 0.0)),
+        options: DrawOptions {
+            draw_wireframe: false,
+            triangle_fill: TriangleFill::Texture,
+            shading: Shading::Phong,
+            backface_culling: false,
+            pause_rendering: true,
+            shuffle_faces: false,
+            tiled: false,
+            texture_filter: TextureFilter::Nearest,
+            shadows: Shadows::None,
+        },
+        lights: default_lights(),
+    };
+
+    // A size that is not a multiple of the tile size, so the last tiles are partial.
+    let (width, height) = (333, 211);
+    for (triangle_fill, draw_wireframe) in [
+        (TriangleFill::Texture, false),
+        (TriangleFill::Color, true),
+        (TriangleFill::None, false),
+    ] {
+        world.options.triangle_fill = triangle_fill;
+        world.options.draw_wireframe = draw_wireframe;
+
+        world.options.tiled = false;
+        let mut serial = Framebuffer::new(width, height, PixelFormatEnum::ARGB8888);
+        draw_meshes(&mut serial, &world);
+
+        world.options.tiled = true;
+        let mut tiled = Framebuffer::new(width, height, PixelFormatEnum::ARGB8888);
+        draw_meshes(&mut tiled, &world);
+
+        assert!(serial.color_buffer == tiled.color_buffer);
+        assert!(serial.z_buffer == tiled.z_buffer);
+        // Make sure there was something to compare.
+        let drawn = (0..width * height)
+            .filter(|i| serial.get_pixel(i % width, i / width) != Color::RGB(0, 0, 0))
+            .count();
+        assert!(drawn > 100);
+    }
+}
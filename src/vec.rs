@@ -1,4 +1,6 @@
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vec2 {
@@ -30,6 +32,57 @@ impl Vec2 {
             y: self.y / len,
         }
     }
+
+    pub fn distance(&self, other: Self) -> f32 {
+        (*self - other).len()
+    }
+
+    // Linear interpolation: self at t = 0.0, other at t = 1.0.
+    pub fn lerp(&self, other: Self, t: f32) -> Self {
+        *self + (other - *self) * t
+    }
+
+    pub fn min(&self, other: Self) -> Self {
+        Self {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+        }
+    }
+
+    pub fn max(&self, other: Self) -> Self {
+        Self {
+            x: self.x.max(other.x),
+            y: self.y.max(other.y),
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            x: self.x.abs(),
+            y: self.y.abs(),
+        }
+    }
+
+    // Mirror the vector off a surface with the given unit normal.
+    pub fn reflect(&self, normal: Self) -> Self {
+        *self - normal * (2.0 * self.dot(normal))
+    }
+
+    // Bend a unit vector entering a surface with the given unit normal, where eta is the ratio of
+    // the refractive indices (outside / inside). None means total internal reflection.
+    pub fn refract(&self, normal: Self, eta: f32) -> Option<Self> {
+        let cos_in = self.dot(normal);
+        let k = 1.0 - eta * eta * (1.0 - cos_in * cos_in);
+        if k < 0.0 {
+            return None;
+        }
+        Some(*self * eta - normal * (eta * cos_in + k.sqrt()))
+    }
+
+    // True when every component is within epsilon of other's.
+    pub fn approx_eq(&self, other: Self, epsilon: f32) -> bool {
+        (self.x - other.x).abs() <= epsilon && (self.y - other.y).abs() <= epsilon
+    }
 }
 
 impl Add for Vec2 {
@@ -100,6 +153,98 @@ impl DivAssign<f32> for Vec2 {
     }
 }
 
+impl Neg for Vec2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        let x = -self.x;
+        let y = -self.y;
+        Self { x, y }
+    }
+}
+
+// Component-wise multiplication.
+impl Mul for Vec2 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let x = self.x * other.x;
+        let y = self.y * other.y;
+        Self { x, y }
+    }
+}
+
+impl MulAssign for Vec2 {
+    fn mul_assign(&mut self, other: Self) {
+        self.x *= other.x;
+        self.y *= other.y;
+    }
+}
+
+// Component-wise division.
+impl Div for Vec2 {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let x = self.x / other.x;
+        let y = self.y / other.y;
+        Self { x, y }
+    }
+}
+
+impl DivAssign for Vec2 {
+    fn div_assign(&mut self, other: Self) {
+        self.x /= other.x;
+        self.y /= other.y;
+    }
+}
+
+impl Index<usize> for Vec2 {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("Vec2 index out of range: {}", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec2 {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("Vec2 index out of range: {}", index),
+        }
+    }
+}
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(v: Vec2) -> Self {
+        [v.x, v.y]
+    }
+}
+
+impl From<Vec2> for (f32, f32) {
+    fn from(v: Vec2) -> Self {
+        (v.x, v.y)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vec3 {
     pub x: f32,
@@ -139,6 +284,62 @@ impl Vec3 {
     pub fn to_vec4(&self) -> Vec4 {
         Vec4::new(self.x, self.y, self.z, 1.0)
     }
+
+    pub fn distance(&self, other: Self) -> f32 {
+        (*self - other).len()
+    }
+
+    // Linear interpolation: self at t = 0.0, other at t = 1.0.
+    pub fn lerp(&self, other: Self, t: f32) -> Self {
+        *self + (other - *self) * t
+    }
+
+    pub fn min(&self, other: Self) -> Self {
+        Self {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+            z: self.z.min(other.z),
+        }
+    }
+
+    pub fn max(&self, other: Self) -> Self {
+        Self {
+            x: self.x.max(other.x),
+            y: self.y.max(other.y),
+            z: self.z.max(other.z),
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            x: self.x.abs(),
+            y: self.y.abs(),
+            z: self.z.abs(),
+        }
+    }
+
+    // Mirror the vector off a surface with the given unit normal.
+    pub fn reflect(&self, normal: Self) -> Self {
+        *self - normal * (2.0 * self.dot(normal))
+    }
+
+    // Bend a unit vector entering a surface with the given unit normal, where eta is the ratio of
+    // the refractive indices (outside / inside). None means total internal reflection.
+    pub fn refract(&self, normal: Self, eta: f32) -> Option<Self> {
+        let cos_in = self.dot(normal);
+        let k = 1.0 - eta * eta * (1.0 - cos_in * cos_in);
+        if k < 0.0 {
+            return None;
+        }
+        Some(*self * eta - normal * (eta * cos_in + k.sqrt()))
+    }
+
+    // True when every component is within epsilon of other's.
+    pub fn approx_eq(&self, other: Self, epsilon: f32) -> bool {
+        (self.x - other.x).abs() <= epsilon
+            && (self.y - other.y).abs() <= epsilon
+            && (self.z - other.z).abs() <= epsilon
+    }
 }
 
 impl Add for Vec3 {
@@ -217,6 +418,105 @@ impl DivAssign<f32> for Vec3 {
     }
 }
 
+impl Neg for Vec3 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        let x = -self.x;
+        let y = -self.y;
+        let z = -self.z;
+        Self { x, y, z }
+    }
+}
+
+// Component-wise multiplication.
+impl Mul for Vec3 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let x = self.x * other.x;
+        let y = self.y * other.y;
+        let z = self.z * other.z;
+        Self { x, y, z }
+    }
+}
+
+impl MulAssign for Vec3 {
+    fn mul_assign(&mut self, other: Self) {
+        self.x *= other.x;
+        self.y *= other.y;
+        self.z *= other.z;
+    }
+}
+
+// Component-wise division.
+impl Div for Vec3 {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let x = self.x / other.x;
+        let y = self.y / other.y;
+        let z = self.z / other.z;
+        Self { x, y, z }
+    }
+}
+
+impl DivAssign for Vec3 {
+    fn div_assign(&mut self, other: Self) {
+        self.x /= other.x;
+        self.y /= other.y;
+        self.z /= other.z;
+    }
+}
+
+impl Index<usize> for Vec3 {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {}", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vec3 index out of range: {}", index),
+        }
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<(f32, f32, f32)> for Vec3 {
+    fn from((x, y, z): (f32, f32, f32)) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Vec3> for [f32; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl From<Vec3> for (f32, f32, f32) {
+    fn from(v: Vec3) -> Self {
+        (v.x, v.y, v.z)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vec4 {
     pub x: f32,
@@ -255,6 +555,66 @@ impl Vec4 {
     pub fn to_vec3(&self) -> Vec3 {
         Vec3::new(self.x, self.y, self.z)
     }
+
+    pub fn distance(&self, other: Self) -> f32 {
+        (*self - other).len()
+    }
+
+    // Linear interpolation: self at t = 0.0, other at t = 1.0.
+    pub fn lerp(&self, other: Self, t: f32) -> Self {
+        *self + (other - *self) * t
+    }
+
+    pub fn min(&self, other: Self) -> Self {
+        Self {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+            z: self.z.min(other.z),
+            w: self.w.min(other.w),
+        }
+    }
+
+    pub fn max(&self, other: Self) -> Self {
+        Self {
+            x: self.x.max(other.x),
+            y: self.y.max(other.y),
+            z: self.z.max(other.z),
+            w: self.w.max(other.w),
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            x: self.x.abs(),
+            y: self.y.abs(),
+            z: self.z.abs(),
+            w: self.w.abs(),
+        }
+    }
+
+    // Mirror the vector off a surface with the given unit normal.
+    pub fn reflect(&self, normal: Self) -> Self {
+        *self - normal * (2.0 * self.dot(normal))
+    }
+
+    // Bend a unit vector entering a surface with the given unit normal, where eta is the ratio of
+    // the refractive indices (outside / inside). None means total internal reflection.
+    pub fn refract(&self, normal: Self, eta: f32) -> Option<Self> {
+        let cos_in = self.dot(normal);
+        let k = 1.0 - eta * eta * (1.0 - cos_in * cos_in);
+        if k < 0.0 {
+            return None;
+        }
+        Some(*self * eta - normal * (eta * cos_in + k.sqrt()))
+    }
+
+    // True when every component is within epsilon of other's.
+    pub fn approx_eq(&self, other: Self, epsilon: f32) -> bool {
+        (self.x - other.x).abs() <= epsilon
+            && (self.y - other.y).abs() <= epsilon
+            && (self.z - other.z).abs() <= epsilon
+            && (self.w - other.w).abs() <= epsilon
+    }
 }
 
 impl Add for Vec4 {
@@ -341,6 +701,112 @@ impl DivAssign<f32> for Vec4 {
     }
 }
 
+impl Neg for Vec4 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        let x = -self.x;
+        let y = -self.y;
+        let z = -self.z;
+        let w = -self.w;
+        Self { x, y, z, w }
+    }
+}
+
+// Component-wise multiplication.
+impl Mul for Vec4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let x = self.x * other.x;
+        let y = self.y * other.y;
+        let z = self.z * other.z;
+        let w = self.w * other.w;
+        Self { x, y, z, w }
+    }
+}
+
+impl MulAssign for Vec4 {
+    fn mul_assign(&mut self, other: Self) {
+        self.x *= other.x;
+        self.y *= other.y;
+        self.z *= other.z;
+        self.w *= other.w;
+    }
+}
+
+// Component-wise division.
+impl Div for Vec4 {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let x = self.x / other.x;
+        let y = self.y / other.y;
+        let z = self.z / other.z;
+        let w = self.w / other.w;
+        Self { x, y, z, w }
+    }
+}
+
+impl DivAssign for Vec4 {
+    fn div_assign(&mut self, other: Self) {
+        self.x /= other.x;
+        self.y /= other.y;
+        self.z /= other.z;
+        self.w /= other.w;
+    }
+}
+
+impl Index<usize> for Vec4 {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            3 => &self.w,
+            _ => panic!("Vec4 index out of range: {}", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec4 {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            3 => &mut self.w,
+            _ => panic!("Vec4 index out of range: {}", index),
+        }
+    }
+}
+
+impl From<[f32; 4]> for Vec4 {
+    fn from([x, y, z, w]: [f32; 4]) -> Self {
+        Self { x, y, z, w }
+    }
+}
+
+impl From<(f32, f32, f32, f32)> for Vec4 {
+    fn from((x, y, z, w): (f32, f32, f32, f32)) -> Self {
+        Self { x, y, z, w }
+    }
+}
+
+impl From<Vec4> for [f32; 4] {
+    fn from(v: Vec4) -> Self {
+        [v.x, v.y, v.z, v.w]
+    }
+}
+
+impl From<Vec4> for (f32, f32, f32, f32) {
+    fn from(v: Vec4) -> Self {
+        (v.x, v.y, v.z, v.w)
+    }
+}
+
 #[test]
 fn test_vec_len() {
     let v2 = Vec2::new(3.0, 4.0);
@@ -535,7 +1001,7 @@ fn test_vec_unit_norm() {
     );
     assert_eq!(
         Vec3::new(2.0, 2.0, 1.0).unit_norm(),
-        Vec3::new(0.66666666, 0.66666666, 0.33333333)
+        Vec3::new(0.6666667, 0.6666667, 0.33333334)
     );
 
     assert_eq!(
@@ -552,6 +1018,193 @@ fn test_vec_unit_norm() {
     );
     assert_eq!(
         Vec4::new(0.0, 2.0, 2.0, 1.0).unit_norm(),
-        Vec4::new(0.0, 0.66666666, 0.66666666, 0.33333333)
+        Vec4::new(0.0, 0.6666667, 0.6666667, 0.33333334)
+    );
+}
+
+#[test]
+fn test_vec_neg() {
+    assert_eq!(-Vec2::new(1.0, -2.0), Vec2::new(-1.0, 2.0));
+    assert_eq!(-Vec3::new(1.0, -2.0, 0.0), Vec3::new(-1.0, 2.0, -0.0));
+    assert_eq!(
+        -Vec4::new(1.0, -2.0, 3.0, 1.0),
+        Vec4::new(-1.0, 2.0, -3.0, -1.0)
+    );
+}
+
+#[test]
+fn test_vec_index() {
+    let mut a = Vec2::new(1.0, 2.0);
+    let mut b = Vec3::new(1.0, 2.0, 3.0);
+    let mut c = Vec4::new(1.0, 2.0, 3.0, 4.0);
+
+    assert_eq!((a[0], a[1]), (1.0, 2.0));
+    assert_eq!((b[0], b[1], b[2]), (1.0, 2.0, 3.0));
+    assert_eq!((c[0], c[1], c[2], c[3]), (1.0, 2.0, 3.0, 4.0));
+
+    a[1] = 5.0;
+    b[2] = 5.0;
+    c[3] = 5.0;
+
+    assert_eq!(a, Vec2::new(1.0, 5.0));
+    assert_eq!(b, Vec3::new(1.0, 2.0, 5.0));
+    assert_eq!(c, Vec4::new(1.0, 2.0, 3.0, 5.0));
+}
+
+#[test]
+#[should_panic]
+fn test_vec_index_out_of_range() {
+    let _ = Vec3::new(1.0, 2.0, 3.0)[3];
+}
+
+#[test]
+fn test_vec_component_mul_div() {
+    let mut a = Vec2::new(1.0, 2.0);
+    let mut b = Vec3::new(1.0, 2.0, 3.0);
+    let mut c = Vec4::new(1.0, 2.0, 3.0, 4.0);
+
+    assert_eq!(a * Vec2::new(2.0, 0.5), Vec2::new(2.0, 1.0));
+    assert_eq!(b * Vec3::new(2.0, 0.5, -1.0), Vec3::new(2.0, 1.0, -3.0));
+    assert_eq!(
+        c * Vec4::new(2.0, 0.5, -1.0, 0.0),
+        Vec4::new(2.0, 1.0, -3.0, 0.0)
+    );
+    assert_eq!(a / Vec2::new(2.0, 0.5), Vec2::new(0.5, 4.0));
+    assert_eq!(b / Vec3::new(2.0, 0.5, -1.0), Vec3::new(0.5, 4.0, -3.0));
+    assert_eq!(
+        c / Vec4::new(2.0, 0.5, -1.0, 4.0),
+        Vec4::new(0.5, 4.0, -3.0, 1.0)
+    );
+
+    a *= Vec2::new(2.0, 3.0);
+    b *= Vec3::new(2.0, 3.0, 4.0);
+    c *= Vec4::new(2.0, 3.0, 4.0, 5.0);
+
+    assert_eq!(a, Vec2::new(2.0, 6.0));
+    assert_eq!(b, Vec3::new(2.0, 6.0, 12.0));
+    assert_eq!(c, Vec4::new(2.0, 6.0, 12.0, 20.0));
+
+    a /= Vec2::new(2.0, 3.0);
+    b /= Vec3::new(2.0, 3.0, 4.0);
+    c /= Vec4::new(2.0, 3.0, 4.0, 5.0);
+
+    assert_eq!(a, Vec2::new(1.0, 2.0));
+    assert_eq!(b, Vec3::new(1.0, 2.0, 3.0));
+    assert_eq!(c, Vec4::new(1.0, 2.0, 3.0, 4.0));
+}
+
+#[test]
+fn test_vec_lerp_distance() {
+    let a = Vec2::new(0.0, 0.0);
+    let b = Vec2::new(3.0, 4.0);
+    assert_eq!(a.lerp(b, 0.0), a);
+    assert_eq!(a.lerp(b, 0.5), Vec2::new(1.5, 2.0));
+    assert_eq!(a.lerp(b, 1.0), b);
+    assert_eq!(a.distance(b), 5.0);
+
+    let c = Vec3::new(1.0, 0.0, 0.0);
+    let d = Vec3::new(1.0, 4.0, 3.0);
+    assert_eq!(c.lerp(d, 0.25), Vec3::new(1.0, 1.0, 0.75));
+    assert_eq!(c.distance(d), 5.0);
+    assert_eq!(d.distance(c), 5.0);
+
+    let e = Vec4::new(0.0, 0.0, 0.0, 1.0);
+    let f = Vec4::new(4.0, 0.0, 0.0, 4.0);
+    assert_eq!(e.lerp(f, 0.5), Vec4::new(2.0, 0.0, 0.0, 2.5));
+    assert_eq!(e.distance(f), 5.0);
+}
+
+#[test]
+fn test_vec_min_max_abs() {
+    let a = Vec2::new(1.0, -2.0);
+    let b = Vec2::new(-1.0, 2.0);
+    assert_eq!(a.min(b), Vec2::new(-1.0, -2.0));
+    assert_eq!(a.max(b), Vec2::new(1.0, 2.0));
+    assert_eq!(a.abs(), Vec2::new(1.0, 2.0));
+
+    let c = Vec3::new(1.0, -2.0, 3.0);
+    let d = Vec3::new(-1.0, 2.0, 3.0);
+    assert_eq!(c.min(d), Vec3::new(-1.0, -2.0, 3.0));
+    assert_eq!(c.max(d), Vec3::new(1.0, 2.0, 3.0));
+    assert_eq!(c.abs(), Vec3::new(1.0, 2.0, 3.0));
+
+    let e = Vec4::new(1.0, -2.0, 3.0, -4.0);
+    let f = Vec4::new(-1.0, 2.0, 3.0, 0.0);
+    assert_eq!(e.min(f), Vec4::new(-1.0, -2.0, 3.0, -4.0));
+    assert_eq!(e.max(f), Vec4::new(1.0, 2.0, 3.0, 0.0));
+    assert_eq!(e.abs(), Vec4::new(1.0, 2.0, 3.0, 4.0));
+}
+
+#[test]
+fn test_vec_reflect_refract() {
+    let normal = Vec2::new(0.0, 1.0);
+    assert_eq!(Vec2::new(1.0, -1.0).reflect(normal), Vec2::new(1.0, 1.0));
+
+    let normal = Vec3::new(0.0, 1.0, 0.0);
+    let v = Vec3::new(1.0, -1.0, 0.0).unit_norm();
+    assert_eq!(v.reflect(normal), Vec3::new(v.x, -v.y, 0.0));
+
+    // Without a change of medium, light goes straight through.
+    assert!(v.refract(normal, 1.0).unwrap().approx_eq(v, 1e-6));
+    // Into a denser medium (air to glass), it bends towards the normal.
+    let refracted = v.refract(normal, 1.0 / 1.5).unwrap();
+    assert!((refracted.len() - 1.0).abs() < 1e-6);
+    assert!(refracted.x > 0.0 && refracted.x < v.x);
+    // Snell's law: sin(in) * n1 = sin(out) * n2.
+    assert!((v.x * 1.0 - refracted.x * 1.5).abs() < 1e-6);
+    // At a shallow angle out of the denser medium, it reflects instead.
+    let shallow = Vec3::new(1.0, -0.1, 0.0).unit_norm();
+    assert_eq!(shallow.refract(normal, 1.5), None);
+
+    let normal = Vec4::new(0.0, 0.0, 1.0, 0.0);
+    assert_eq!(
+        Vec4::new(1.0, 0.0, -1.0, 0.0).reflect(normal),
+        Vec4::new(1.0, 0.0, 1.0, 0.0)
+    );
+    assert_eq!(
+        Vec2::new(0.0, -1.0).refract(Vec2::new(0.0, 1.0), 1.5),
+        Some(Vec2::new(0.0, -1.0))
+    );
+}
+
+#[test]
+fn test_vec_approx_eq() {
+    assert!(Vec2::new(1.0, 2.0).approx_eq(Vec2::new(1.0005, 1.9995), 0.001));
+    assert!(!Vec2::new(1.0, 2.0).approx_eq(Vec2::new(1.0, 2.01), 0.001));
+    assert!(Vec3::new(1.0, 2.0, 3.0).approx_eq(Vec3::new(1.0, 2.0, 3.0), 0.0));
+    assert!(!Vec3::new(1.0, 2.0, 3.0).approx_eq(Vec3::new(1.0, 2.0, 3.1), 0.01));
+    assert!(Vec4::new(0.1, 0.2, 0.3, 0.0).approx_eq(Vec4::new(0.1, 0.2, 0.3, 1e-7), 1e-6));
+    assert!(!Vec4::new(0.0, 0.0, 0.0, 0.0).approx_eq(Vec4::new(0.0, 0.0, 0.0, 1.0), 0.5));
+}
+
+#[test]
+fn test_vec_from() {
+    assert_eq!(Vec2::from([1.0, 2.0]), Vec2::new(1.0, 2.0));
+    assert_eq!(Vec2::from((1.0, 2.0)), Vec2::new(1.0, 2.0));
+    assert_eq!(<[f32; 2]>::from(Vec2::new(1.0, 2.0)), [1.0, 2.0]);
+    assert_eq!(<(f32, f32)>::from(Vec2::new(1.0, 2.0)), (1.0, 2.0));
+
+    assert_eq!(Vec3::from([1.0, 2.0, 3.0]), Vec3::new(1.0, 2.0, 3.0));
+    assert_eq!(Vec3::from((1.0, 2.0, 3.0)), Vec3::new(1.0, 2.0, 3.0));
+    let array: [f32; 3] = Vec3::new(1.0, 2.0, 3.0).into();
+    assert_eq!(array, [1.0, 2.0, 3.0]);
+    let tuple: (f32, f32, f32) = Vec3::new(1.0, 2.0, 3.0).into();
+    assert_eq!(tuple, (1.0, 2.0, 3.0));
+
+    assert_eq!(
+        Vec4::from([1.0, 2.0, 3.0, 4.0]),
+        Vec4::new(1.0, 2.0, 3.0, 4.0)
+    );
+    assert_eq!(
+        Vec4::from((1.0, 2.0, 3.0, 4.0)),
+        Vec4::new(1.0, 2.0, 3.0, 4.0)
+    );
+    assert_eq!(
+        <[f32; 4]>::from(Vec4::new(1.0, 2.0, 3.0, 4.0)),
+        [1.0, 2.0, 3.0, 4.0]
+    );
+    assert_eq!(
+        <(f32, f32, f32, f32)>::from(Vec4::new(1.0, 2.0, 3.0, 4.0)),
+        (1.0, 2.0, 3.0, 4.0)
     );
 }
@@ -0,0 +1,583 @@
+// Golden image tests: render fixed scenes offscreen and compare them against the reference
+// images checked in to tests/golden/.
+//
+// After an intentional change to the rendered output, regenerate the reference images with:
+//   UPDATE_GOLDEN=1 cargo test --test golden
+// and review the new images before committing them.
+use graphics_from_scratch::camera::{Camera, Projection};
+use graphics_from_scratch::framebuffer::Framebuffer;
+use graphics_from_scratch::gltf_import::load_gltf;
+use graphics_from_scratch::light::{default_lights, Attenuation, Light};
+use graphics_from_scratch::material::AlphaMode;
+use graphics_from_scratch::mesh::Mesh;
+use graphics_from_scratch::quat::Quat;
+use graphics_from_scratch::shadow::Shadows;
+use graphics_from_scratch::texture::{Texture, TextureFilter};
+use graphics_from_scratch::vec::Vec3;
+use graphics_from_scratch::{draw_meshes, DrawOptions, MeshPosition, Shading, TriangleFill, World};
+use image::{ImageBuffer, Rgba, RgbaImage};
+use sdl2::pixels::{Color, PixelFormatEnum};
+
+const WIDTH: u32 = 420;
+const HEIGHT: u32 = 180;
+// The largest difference allowed in any color channel of a pixel.
+// This absorbs small floating point differences between platforms.
+const CHANNEL_TOLERANCE: u8 = 2;
+
+const ASSET_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets");
+
+fn render_scene(model: &str, texture: &str, options: DrawOptions) -> RgbaImage {
+    render_world(
+        vec![MeshPosition::new(
+            load_obj(model, texture),
+            Vec3::new(0.0, 0.0, 0.0),
+        )],
+        camera(),
+        options,
+        default_lights(),
+    )
+}
+
+fn camera() -> Camera {
+    Camera::new(Vec3::new(2.0, 1.5, -3.5), Vec3::new(0.0, 0.0, 0.0))
+}
+
+fn load_obj(model: &str, texture: &str) -> Mesh {
+    Mesh::load_mesh(
+        &format!("{}/{}.obj", ASSET_DIR, model),
+        &format!("{}/{}.png", ASSET_DIR, texture),
+    )
+    .unwrap()
+}
+
+// Every scene is also drawn with tiled rendering, which must give exactly the same pixels.
+fn render_world(
+    meshes: Vec<MeshPosition>,
+    camera: Camera,
+    options: DrawOptions,
+    lights: Vec<Light>,
+) -> RgbaImage {
+    let mut world = World {
+        meshes,
+        camera,
+        options,
+        lights,
+    };
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT, PixelFormatEnum::ARGB8888);
+    draw_meshes(&mut framebuffer, &world);
+
+    world.options.tiled = !world.options.tiled;
+    let mut other_framebuffer = Framebuffer::new(WIDTH, HEIGHT, PixelFormatEnum::ARGB8888);
+    draw_meshes(&mut other_framebuffer, &world);
+    assert!(framebuffer.color_buffer == other_framebuffer.color_buffer);
+    assert!(framebuffer.z_buffer == other_framebuffer.z_buffer);
+
+    framebuffer.to_image()
+}
+
+fn channel_difference(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap()
+}
+
+fn check_golden(name: &str, model: &str, texture: &str, options: DrawOptions) {
+    check_image(name, render_scene(model, texture, options));
+}
+
+// glTF files bring their own textures, and their node transforms place the meshes.
+// The glTF models are conversions of OBJ models, so instead of having golden images of their
+// own they are compared against the render of the OBJ model (with its texture of the same name).
+fn check_gltf_matches_obj(
+    name: &str,
+    file_name: &str,
+    model: &str,
+    options: impl Fn() -> DrawOptions,
+) {
+    let meshes = load_gltf(&format!("{}/{}", ASSET_DIR, file_name)).unwrap();
+    let actual = render_world(meshes, camera(), options(), default_lights());
+    let expected = render_scene(model, model, options());
+    compare_images(
+        name,
+        &actual,
+        &expected,
+        &format!("the render of {}.obj", model),
+    );
+}
+
+fn check_image(name: &str, actual: RgbaImage) {
+    let golden_path = format!("{}/tests/golden/{}.png", env!("CARGO_MANIFEST_DIR"), name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&golden_path).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&golden_path) {
+        Ok(image) => image.into_rgba8(),
+        Err(e) => panic!(
+            "Could not open golden image {}: {}. Run with UPDATE_GOLDEN=1 to create it.",
+            golden_path, e
+        ),
+    };
+    compare_images(name, &actual, &expected, &golden_path);
+}
+
+fn compare_images(name: &str, actual: &RgbaImage, expected: &RgbaImage, expected_name: &str) {
+    assert_eq!(actual.dimensions(), expected.dimensions());
+
+    // The diff image shows the expected image dimmed, with mismatched pixels in red.
+    let mut mismatched_pixels = 0;
+    let diff: RgbaImage = ImageBuffer::from_fn(WIDTH, HEIGHT, |x, y| {
+        let a = actual.get_pixel(x, y);
+        let e = expected.get_pixel(x, y);
+        if channel_difference(a, e) > CHANNEL_TOLERANCE {
+            mismatched_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            Rgba([e[0] / 4, e[1] / 4, e[2] / 4, 255])
+        }
+    });
+
+    if mismatched_pixels > 0 {
+        let output_dir = format!("{}/golden", env!("CARGO_TARGET_TMPDIR"));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let actual_path = format!("{}/{}_actual.png", output_dir, name);
+        let diff_path = format!("{}/{}_diff.png", output_dir, name);
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{} pixels differ from {}. See {} and {}",
+            mismatched_pixels, expected_name, actual_path, diff_path
+        );
+    }
+}
+
+fn options(
+    triangle_fill: TriangleFill,
+    draw_wireframe: bool,
+    backface_culling: bool,
+) -> DrawOptions {
+    DrawOptions {
+        draw_wireframe,
+        triangle_fill,
+        shading: Shading::Flat,
+        backface_culling,
+        pause_rendering: true,
+        shuffle_faces: false,
+        tiled: false,
+        texture_filter: TextureFilter::Nearest,
+        shadows: Shadows::None,
+    }
+}
+
+#[test]
+fn golden_cube_points() {
+    check_golden(
+        "cube_points",
+        "cube",
+        "cube",
+        options(TriangleFill::None, false, true),
+    );
+}
+
+#[test]
+fn golden_cube_wireframe() {
+    check_golden(
+        "cube_wireframe",
+        "cube",
+        "cube",
+        options(TriangleFill::None, true, true),
+    );
+}
+
+#[test]
+fn golden_cube_wireframe_no_culling() {
+    check_golden(
+        "cube_wireframe_no_culling",
+        "cube",
+        "cube",
+        options(TriangleFill::None, true, false),
+    );
+}
+
+#[test]
+fn golden_cube_color() {
+    check_golden(
+        "cube_color",
+        "cube",
+        "cube",
+        options(TriangleFill::Color, false, true),
+    );
+}
+
+#[test]
+fn golden_cube_texture() {
+    check_golden(
+        "cube_texture",
+        "cube",
+        "cube",
+        options(TriangleFill::Texture, false, true),
+    );
+}
+
+#[test]
+fn golden_cube_texture_wireframe() {
+    check_golden(
+        "cube_texture_wireframe",
+        "cube",
+        "cube",
+        options(TriangleFill::Texture, true, true),
+    );
+}
+
+// A non-uniform, negative scale mirrors the cube, which must not turn it inside out.
+#[test]
+fn golden_cube_texture_transformed() {
+    let mesh = load_obj("cube", "cube");
+    let mesh_position = MeshPosition {
+        rotation: Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), 20.0)
+            * Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 45.0),
+        scale: Vec3::new(1.5, 0.75, -1.0),
+        ..MeshPosition::new(mesh, Vec3::new(0.5, 0.0, 0.0))
+    };
+    check_image(
+        "cube_texture_transformed",
+        render_world(
+            vec![mesh_position],
+            camera(),
+            options(TriangleFill::Texture, true, true),
+            default_lights(),
+        ),
+    );
+}
+
+fn check_golden_orthographic(name: &str, model: &str, texture: &str, options: DrawOptions) {
+    let camera = Camera {
+        projection: Projection::Orthographic,
+        extent: 3.0,
+        ..camera()
+    };
+    let meshes = vec![MeshPosition::new(
+        load_obj(model, texture),
+        Vec3::new(0.0, 0.0, 0.0),
+    )];
+    check_image(
+        name,
+        render_world(meshes, camera, options, default_lights()),
+    );
+}
+
+#[test]
+fn golden_cube_orthographic_texture_wireframe() {
+    check_golden_orthographic(
+        "cube_orthographic_texture_wireframe",
+        "cube",
+        "cube",
+        options(TriangleFill::Texture, true, true),
+    );
+}
+
+#[test]
+fn golden_f22_orthographic_color() {
+    check_golden_orthographic(
+        "f22_orthographic_color",
+        "f22",
+        "f22",
+        options(TriangleFill::Color, false, true),
+    );
+}
+
+#[test]
+fn golden_cube_gltf_texture() {
+    check_gltf_matches_obj("cube_gltf_texture", "cube.gltf", "cube", || {
+        options(TriangleFill::Texture, false, true)
+    });
+}
+
+#[test]
+fn golden_cube_glb_texture() {
+    check_gltf_matches_obj("cube_glb_texture", "cube.glb", "cube", || {
+        options(TriangleFill::Texture, false, true)
+    });
+}
+
+#[test]
+fn golden_f22_points() {
+    check_golden(
+        "f22_points",
+        "f22",
+        "f22",
+        options(TriangleFill::None, false, true),
+    );
+}
+
+#[test]
+fn golden_f22_wireframe() {
+    check_golden(
+        "f22_wireframe",
+        "f22",
+        "f22",
+        options(TriangleFill::None, true, true),
+    );
+}
+
+#[test]
+fn golden_f22_color() {
+    check_golden(
+        "f22_color",
+        "f22",
+        "f22",
+        options(TriangleFill::Color, false, true),
+    );
+}
+
+#[test]
+fn golden_f22_color_no_culling() {
+    check_golden(
+        "f22_color_no_culling",
+        "f22",
+        "f22",
+        options(TriangleFill::Color, false, false),
+    );
+}
+
+#[test]
+fn golden_f22_texture() {
+    check_golden(
+        "f22_texture",
+        "f22",
+        "f22",
+        options(TriangleFill::Texture, false, true),
+    );
+}
+
+#[test]
+fn golden_f22_texture_wireframe() {
+    check_golden(
+        "f22_texture_wireframe",
+        "f22",
+        "f22",
+        options(TriangleFill::Texture, true, true),
+    );
+}
+
+#[test]
+fn golden_sphere_color_flat() {
+    check_golden(
+        "sphere_color_flat",
+        "sphere",
+        "pikuma",
+        options(TriangleFill::Color, false, true),
+    );
+}
+
+#[test]
+fn golden_sphere_color_gouraud() {
+    check_golden(
+        "sphere_color_gouraud",
+        "sphere",
+        "pikuma",
+        DrawOptions {
+            shading: Shading::Gouraud,
+            ..options(TriangleFill::Color, false, true)
+        },
+    );
+}
+
+#[test]
+fn golden_sphere_color_phong() {
+    check_golden(
+        "sphere_color_phong",
+        "sphere",
+        "pikuma",
+        DrawOptions {
+            shading: Shading::Phong,
+            ..options(TriangleFill::Color, false, true)
+        },
+    );
+}
+
+#[test]
+fn golden_sphere_colored_lights() {
+    // A red point light below to the right, a blue spot light from above the camera lighting a
+    // circle on the front of the sphere, and a little ambient light.
+    let lights = vec![
+        Light::Ambient {
+            color: Color::RGB(255, 255, 255),
+            intensity: 0.25,
+        },
+        Light::Point {
+            position: Vec3::new(3.5, -1.0, -2.0),
+            color: Color::RGB(255, 0, 0),
+            intensity: 3.0,
+            attenuation: Attenuation {
+                constant: 0.0,
+                linear: 0.0,
+                quadratic: 0.5,
+            },
+        },
+        Light::Spot {
+            position: Vec3::new(0.0, 4.0, -4.0),
+            direction: Vec3::new(0.0, -1.0, 1.0),
+            inner_angle: 8.0,
+            outer_angle: 14.0,
+            color: Color::RGB(0, 100, 255),
+            intensity: 1.5,
+            attenuation: Attenuation::NONE,
+        },
+    ];
+    let meshes = vec![MeshPosition::new(
+        load_obj("sphere", "pikuma"),
+        Vec3::new(0.0, 0.0, 0.0),
+    )];
+    let options = DrawOptions {
+        shading: Shading::Phong,
+        ..options(TriangleFill::Color, false, true)
+    };
+    check_image(
+        "sphere_colored_lights",
+        render_world(meshes, camera(), options, lights),
+    );
+}
+
+#[test]
+fn golden_sphere_specular() {
+    // White highlights from the default lights, narrower per pixel than per vertex.
+    let mut sphere = load_obj("sphere", "pikuma");
+    for material in &mut sphere.materials {
+        material.specular = Color::RGB(255, 255, 255);
+        material.shininess = 32.0;
+    }
+    for (name, shading) in [
+        ("sphere_specular_gouraud", Shading::Gouraud),
+        ("sphere_specular_phong", Shading::Phong),
+    ] {
+        let meshes = vec![MeshPosition::new(sphere.clone(), Vec3::new(0.0, 0.0, 0.0))];
+        let options = DrawOptions {
+            shading,
+            ..options(TriangleFill::Color, false, true)
+        };
+        check_image(
+            name,
+            render_world(meshes, camera(), options, default_lights()),
+        );
+    }
+}
+
+#[test]
+fn golden_cube_shadows() {
+    // A cube floating above a wide, flat box, lit by the default lights and a spot light from the
+    // left, so there are two shadows on the floor.
+    let meshes = || {
+        let mut cube = MeshPosition::new(load_obj("cube", "cube"), Vec3::new(0.0, 0.2, 0.0));
+        cube.scale = Vec3::new(0.6, 0.6, 0.6);
+        let mut floor = MeshPosition::new(load_obj("cube", "cube"), Vec3::new(0.0, -1.6, 0.0));
+        floor.scale = Vec3::new(4.0, 0.1, 4.0);
+        vec![cube, floor]
+    };
+    let mut lights = default_lights();
+    lights.push(Light::Spot {
+        position: Vec3::new(-4.0, 3.0, 0.0),
+        direction: Vec3::new(1.0, -1.0, 0.0),
+        inner_angle: 30.0,
+        outer_angle: 40.0,
+        color: Color::RGB(255, 200, 120),
+        intensity: 1.0,
+        attenuation: Attenuation::NONE,
+    });
+    for (name, shadows) in [
+        ("cube_shadows_hard", Shadows::Hard),
+        ("cube_shadows_pcf", Shadows::Pcf),
+    ] {
+        let options = DrawOptions {
+            shading: Shading::Phong,
+            shadows,
+            ..options(TriangleFill::Color, false, true)
+        };
+        check_image(
+            name,
+            render_world(
+                meshes(),
+                Camera::new(Vec3::new(2.0, 3.0, -5.0), Vec3::new(0.0, -0.5, 0.0)),
+                options,
+                lights.clone(),
+            ),
+        );
+    }
+}
+
+#[test]
+fn golden_alpha_mask_and_blend() {
+    // A cube with square holes cut out of its faces, and a see-through sphere in front of it.
+    // Back faces are drawn, so the inside of the cube shows through the holes and the back of
+    // the sphere through its front.
+    let mut cube = load_obj("cube", "cube");
+    cube.texture = Texture::new(ImageBuffer::from_fn(8, 8, |x, y| {
+        if (x / 2 + y / 2) % 2 == 0 {
+            Rgba([230, 160, 40, 255])
+        } else {
+            Rgba([0, 0, 0, 0])
+        }
+    }));
+    for material in &mut cube.materials {
+        material.alpha_mode = AlphaMode::Mask(0.5);
+    }
+    let mut sphere = load_obj("sphere", "pikuma");
+    for material in &mut sphere.materials {
+        material.opacity = 0.4;
+        material.alpha_mode = AlphaMode::Blend;
+    }
+    let mut sphere = MeshPosition::new(sphere, Vec3::new(0.8, 0.3, -1.2));
+    sphere.scale = Vec3::new(0.4, 0.4, 0.4);
+    let meshes = vec![MeshPosition::new(cube, Vec3::new(-0.5, 0.0, 0.0)), sphere];
+    let options = DrawOptions {
+        shading: Shading::Phong,
+        ..options(TriangleFill::Texture, false, false)
+    };
+    check_image(
+        "alpha_mask_and_blend",
+        render_world(meshes, camera(), options, default_lights()),
+    );
+}
+
+#[test]
+fn golden_sphere_texture_phong() {
+    check_golden(
+        "sphere_texture_phong",
+        "sphere",
+        "pikuma",
+        DrawOptions {
+            shading: Shading::Phong,
+            ..options(TriangleFill::Texture, false, true)
+        },
+    );
+}
+
+#[test]
+fn golden_cube_texture_bilinear() {
+    check_golden(
+        "cube_texture_bilinear",
+        "cube",
+        "cube",
+        DrawOptions {
+            texture_filter: TextureFilter::Bilinear,
+            ..options(TriangleFill::Texture, false, true)
+        },
+    );
+}
+
+#[test]
+fn golden_f22_texture_trilinear() {
+    check_golden(
+        "f22_texture_trilinear",
+        "f22",
+        "f22",
+        DrawOptions {
+            texture_filter: TextureFilter::Trilinear,
+            ..options(TriangleFill::Texture, false, true)
+        },
+    );
+}